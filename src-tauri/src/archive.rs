@@ -0,0 +1,234 @@
+use crate::storage::{self, AppConfig, Context, FullBackup, ImportReport, ImportStrategy, ProfileData};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::fs;
+use std::io::{Read, Write};
+use std::path::Path;
+use tauri::AppHandle;
+
+/// Bumped whenever the archive layout changes in a way older readers can't handle.
+pub const ARCHIVE_SCHEMA_VERSION: u32 = 1;
+
+const MANIFEST_PATH: &str = "manifest.json";
+const CONFIG_PATH: &str = "config.json";
+const COMMON_PATH: &str = "common.hosts";
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ArchiveManifest {
+    pub schema_version: u32,
+    pub created: String,
+    pub files: Vec<ArchiveFile>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ArchiveFile {
+    pub path: String,
+    pub sha256: String,
+    pub size: u64,
+}
+
+fn sha256_hex(data: &[u8]) -> String {
+    format!("{:x}", Sha256::digest(data))
+}
+
+fn profile_path(id: &str) -> String {
+    format!("profiles/{}.hosts", id)
+}
+
+#[tauri::command]
+pub fn export_archive(app: AppHandle, path: String) -> Result<(), String> {
+    export_archive_internal(&Context::Tauri(&app), Path::new(&path))
+}
+
+/// Writes a tar archive with a checksummed manifest, the config, the common config and
+/// every profile as its own hosts file, so large lists stay diffable.
+pub fn export_archive_internal(ctx: &Context, target: &Path) -> Result<(), String> {
+    let config = storage::exportable_config(storage::load_config_internal(ctx)?);
+    let common = storage::load_common_config_internal(ctx)?;
+
+    let mut files: Vec<(String, Vec<u8>)> = vec![
+        (CONFIG_PATH.to_string(), serde_json::to_vec_pretty(&config).map_err(|e| e.to_string())?),
+        (COMMON_PATH.to_string(), common.into_bytes()),
+    ];
+    for p in &config.profiles {
+        files.push((profile_path(&p.id), storage::read_profile_file_internal(ctx, &p.id)?.into_bytes()));
+    }
+
+    let file = fs::File::create(target).map_err(|e| format!("Failed to create archive: {}", e))?;
+    write_archive(file, files)
+}
+
+/// Prepends the manifest for `files` and writes everything as a tar stream.
+fn write_archive<W: Write>(out: W, files: Vec<(String, Vec<u8>)>) -> Result<(), String> {
+    let manifest = ArchiveManifest {
+        schema_version: ARCHIVE_SCHEMA_VERSION,
+        created: chrono::Local::now().to_rfc3339(),
+        files: files.iter()
+            .map(|(path, data)| ArchiveFile { path: path.clone(), sha256: sha256_hex(data), size: data.len() as u64 })
+            .collect(),
+    };
+    let manifest = serde_json::to_vec_pretty(&manifest).map_err(|e| e.to_string())?;
+
+    let mut builder = tar::Builder::new(out);
+    let mtime = chrono::Utc::now().timestamp().max(0) as u64;
+    for (path, data) in std::iter::once((MANIFEST_PATH.to_string(), manifest)).chain(files) {
+        let mut header = tar::Header::new_ustar();
+        header.set_size(data.len() as u64);
+        header.set_mode(0o644);
+        header.set_mtime(mtime);
+        builder.append_data(&mut header, &path, data.as_slice()).map_err(|e| format!("Failed to write {}: {}", path, e))?;
+    }
+    builder.into_inner().map_err(|e| format!("Failed to finish archive: {}", e))?;
+    Ok(())
+}
+
+fn read_archive<R: Read>(input: R) -> Result<HashMap<String, Vec<u8>>, String> {
+    let mut archive = tar::Archive::new(input);
+    let mut entries: HashMap<String, Vec<u8>> = HashMap::new();
+    for entry in archive.entries().map_err(|e| format!("Invalid archive: {}", e))? {
+        let mut entry = entry.map_err(|e| format!("Invalid archive: {}", e))?;
+        if !entry.header().entry_type().is_file() {
+            continue;
+        }
+        let path = entry.path().map_err(|e| format!("Invalid archive: {}", e))?.to_string_lossy().to_string();
+        let mut data = Vec::new();
+        entry.read_to_end(&mut data).map_err(|e| format!("Failed to read {}: {}", path, e))?;
+        entries.insert(path, data);
+    }
+    Ok(entries)
+}
+
+/// Checks the manifest and every checksum in it, then drops whatever the manifest doesn't
+/// vouch for.
+fn verify_entries(mut entries: HashMap<String, Vec<u8>>) -> Result<(ArchiveManifest, HashMap<String, Vec<u8>>), String> {
+    let manifest: ArchiveManifest = entries.get(MANIFEST_PATH)
+        .ok_or("Invalid archive: manifest.json missing")
+        .and_then(|m| serde_json::from_slice(m).map_err(|_| "Invalid archive: unreadable manifest.json"))?;
+    if manifest.schema_version > ARCHIVE_SCHEMA_VERSION {
+        return Err(format!(
+            "Archive schema version {} is newer than supported ({}), please update Hostly",
+            manifest.schema_version, ARCHIVE_SCHEMA_VERSION
+        ));
+    }
+
+    for f in &manifest.files {
+        let data = entries.get(&f.path).ok_or_else(|| format!("Archive is missing {}", f.path))?;
+        if data.len() as u64 != f.size || !sha256_hex(data).eq_ignore_ascii_case(&f.sha256) {
+            return Err(format!("Checksum mismatch for {}", f.path));
+        }
+    }
+    entries.retain(|path, _| manifest.files.iter().any(|f| &f.path == path));
+    Ok((manifest, entries))
+}
+
+#[tauri::command]
+pub fn import_archive(
+    app: AppHandle,
+    path: String,
+    strategy: Option<ImportStrategy>,
+    dry_run: Option<bool>
+) -> Result<ImportReport, String> {
+    let dry_run = dry_run.unwrap_or(false);
+    let report = import_archive_internal(&Context::Tauri(&app), Path::new(&path), strategy.unwrap_or_default(), dry_run)?;
+    if !dry_run {
+        storage::apply_config(app)?;
+    }
+    Ok(report)
+}
+
+/// Verifies every checksum in the manifest before handing the contents to the regular
+/// backup import, so a truncated or edited archive never gets half applied.
+pub fn import_archive_internal(ctx: &Context, source: &Path, strategy: ImportStrategy, dry_run: bool) -> Result<ImportReport, String> {
+    let file = fs::File::open(source).map_err(|e| format!("Failed to open archive: {}", e))?;
+    let (manifest, entries) = verify_entries(read_archive(file)?)?;
+    let verified = |path: &str| entries.get(path).map(|d| String::from_utf8_lossy(d).to_string());
+
+    let config: AppConfig = verified(CONFIG_PATH)
+        .ok_or("Invalid archive: config.json missing")
+        .and_then(|c| serde_json::from_str(&c).map_err(|_| "Invalid archive: unreadable config.json"))?;
+
+    let mut profiles = Vec::new();
+    for p in &config.profiles {
+        if !storage::is_safe_profile_id(&p.id) {
+            return Err(format!("Invalid archive: unsafe profile ID {:?}", p.id));
+        }
+        if let Some(content) = verified(&profile_path(&p.id)) {
+            profiles.push(ProfileData { id: p.id.clone(), name: p.name.clone(), content, active: p.active, remote: None });
+        }
+    }
+
+    let backup = FullBackup {
+        version: 2,
+        timestamp: manifest.created.clone(),
+        config,
+        profiles: Some(profiles),
+        profiles_content: None,
+    };
+    let mut report = storage::import_backup_internal(ctx, backup, strategy, dry_run)?;
+
+    if let Some(common) = verified(COMMON_PATH) {
+        // Merging strategies keep the local common config, replace-all takes the archived one
+        if strategy == ImportStrategy::ReplaceAll {
+            if !dry_run {
+                storage::save_common_config_internal(ctx, common)?;
+            }
+        } else if common != storage::load_common_config_internal(ctx).unwrap_or_default() {
+            report.warnings.push("Common config in the archive differs and was not imported".to_string());
+        }
+    }
+
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pack(files: Vec<(&str, &str)>) -> HashMap<String, Vec<u8>> {
+        let files = files.into_iter().map(|(p, d)| (p.to_string(), d.as_bytes().to_vec())).collect();
+        let mut buf = Vec::new();
+        write_archive(&mut buf, files).unwrap();
+        read_archive(buf.as_slice()).unwrap()
+    }
+
+    #[test]
+    fn round_trip_verifies() {
+        let entries = pack(vec![(CONFIG_PATH, "{}"), ("profiles/a.hosts", "1.1.1.1 a")]);
+        let (manifest, entries) = verify_entries(entries).unwrap();
+        assert_eq!(manifest.files.len(), 2);
+        assert_eq!(entries["profiles/a.hosts"], b"1.1.1.1 a");
+    }
+
+    #[test]
+    fn tampered_file_is_rejected() {
+        let mut entries = pack(vec![("profiles/a.hosts", "1.1.1.1 a")]);
+        entries.insert("profiles/a.hosts".into(), b"6.6.6.6 a".to_vec());
+        assert_eq!(verify_entries(entries).unwrap_err(), "Checksum mismatch for profiles/a.hosts");
+    }
+
+    #[test]
+    fn missing_file_is_rejected() {
+        let mut entries = pack(vec![("profiles/a.hosts", "1.1.1.1 a")]);
+        entries.remove("profiles/a.hosts");
+        assert_eq!(verify_entries(entries).unwrap_err(), "Archive is missing profiles/a.hosts");
+    }
+
+    #[test]
+    fn unlisted_files_are_dropped() {
+        let mut entries = pack(vec![(CONFIG_PATH, "{}")]);
+        entries.insert("profiles/x.hosts".into(), b"6.6.6.6 x".to_vec());
+        let (_, entries) = verify_entries(entries).unwrap();
+        assert!(!entries.contains_key("profiles/x.hosts"));
+        assert!(!entries.contains_key(MANIFEST_PATH));
+    }
+
+    #[test]
+    fn newer_schema_and_missing_manifest_are_rejected() {
+        let mut entries = pack(vec![]);
+        let manifest = ArchiveManifest { schema_version: ARCHIVE_SCHEMA_VERSION + 1, created: String::new(), files: vec![] };
+        entries.insert(MANIFEST_PATH.into(), serde_json::to_vec(&manifest).unwrap());
+        assert!(verify_entries(entries).unwrap_err().contains("newer than supported"));
+        assert!(verify_entries(HashMap::new()).unwrap_err().contains("manifest.json missing"));
+    }
+}
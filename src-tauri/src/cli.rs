@@ -1,7 +1,20 @@
-use clap::{Parser, Subcommand};
+use clap::{CommandFactory, FromArgMatches, Parser, Subcommand, ValueEnum};
 use crate::storage;
+use crate::switchhosts;
+use crate::archive;
+use crate::git_sync;
+use crate::remote;
+use crate::control::{self, ControlSettings};
+use crate::scheduler;
+use crate::daemon::{self, DaemonOptions};
+use crate::logging::LogFormat;
+use crate::formats::SourceFormat;
+use crate::transform::{HostPattern, TransformRules};
+use crate::proxy::{self, ProxyMode, ProxySettings};
+use serde::Serialize;
+use serde_json::{json, Value};
 use tauri::AppHandle;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::fs;
 
 
@@ -33,6 +46,23 @@ fn check_elevation() {
 struct Cli {
     #[command(subcommand)]
     command: Option<Commands>,
+
+    /// How results are printed
+    #[arg(long, value_enum, global = true, default_value_t = OutputFormat::Text)]
+    output: OutputFormat,
+
+    /// Only print warnings and errors (text output)
+    #[arg(long, short, global = true)]
+    quiet: bool,
+}
+
+#[derive(Clone, Copy, PartialEq, ValueEnum)]
+enum OutputFormat {
+    /// Human-readable lines
+    Text,
+    /// One JSON document on stdout with `command`, `ok`, command specific fields, `results`,
+    /// `warnings` and `errors`
+    Json,
 }
 
 #[derive(Subcommand)]
@@ -67,6 +97,10 @@ enum Commands {
         /// Output file path
         #[arg(long, short, required = true)]
         target: String,
+
+        /// Backup format used for full exports
+        #[arg(long, value_enum, default_value_t = ExportFormat::Json)]
+        format: ExportFormat,
     },
     /// Import profile or common config
     Import {
@@ -88,183 +122,506 @@ enum Commands {
         /// Force single-mode if needed
         #[arg(long, short)]
         single: bool,
+
+        /// How a full backup (.json) is merged with the existing profiles
+        #[arg(long, value_enum, default_value_t = storage::ImportStrategy::ReplaceAll)]
+        strategy: storage::ImportStrategy,
+
+        /// Only report what a full backup import would change
+        #[arg(long)]
+        dry_run: bool,
     },
     /// Migrate from SwitchHosts
     Migration {
         /// SwitchHosts backup file path (json)
         #[arg(long, short, required = true)]
         target: String,
+    },
+    /// Recover common config and profiles from the current (Hostly-generated) system hosts file
+    Adopt,
+    /// Sync profiles with the configured git working tree
+    Git {
+        #[command(subcommand)]
+        action: GitAction,
+    },
+    /// Configure remote profiles
+    Remote {
+        #[command(subcommand)]
+        action: RemoteAction,
+    },
+    /// Show or change the proxy used for remote downloads
+    Proxy {
+        #[command(subcommand)]
+        action: Option<ProxyAction>,
+    },
+    /// Switch how profile files are stored on disk, moving the existing files
+    Layout {
+        #[arg(value_enum)]
+        layout: storage::StorageLayout,
+    },
+    /// Show or change the local control API (loopback HTTP for scripts)
+    Control {
+        #[command(subcommand)]
+        action: Option<ControlAction>,
+    },
+    /// Refresh remote profiles once (only the ones that are due by default), for cron
+    Update {
+        /// Profile to refresh, even if it is not due yet
+        #[arg(conflicts_with = "all")]
+        name: Option<String>,
+
+        /// Refresh every remote profile, even if it is not due yet
+        #[arg(long)]
+        all: bool,
+    },
+    /// Keep refreshing remote profiles and watching files/networks in the foreground
+    Daemon {
+        /// Write the process id here, refuse to start if it belongs to a running daemon
+        #[arg(long)]
+        pidfile: Option<PathBuf>,
+
+        /// Log line format
+        #[arg(long, value_enum, default_value = "text")]
+        log_format: LogFormat,
+    },
+}
+
+#[derive(Subcommand)]
+enum GitAction {
+    /// Merge remote changes into the working tree and import them
+    Pull,
+    /// Push committed profile changes to the remote
+    Push,
+}
+
+#[derive(Subcommand)]
+enum RemoteAction {
+    /// Set the format a remote profile's sources are published in
+    Format {
+        /// Profile name
+        name: String,
+        #[arg(value_enum)]
+        format: SourceFormat,
+        /// Only this source URL (default: every source of the profile)
+        #[arg(long)]
+        url: Option<String>,
+        /// IP converted entries point at, e.g. 0.0.0.0, 127.0.0.1 or ::
+        #[arg(long)]
+        sink: Option<String>,
+    },
+    /// Set the filters and rewrites applied to a remote profile's content
+    Transform {
+        /// Profile name
+        name: String,
+        /// Keep only this domain and its subdomains (repeatable)
+        #[arg(long)]
+        include_suffix: Vec<String>,
+        /// Keep only hostnames matching this regex (repeatable)
+        #[arg(long)]
+        include_regex: Vec<String>,
+        /// Drop this domain and its subdomains (repeatable)
+        #[arg(long)]
+        exclude_suffix: Vec<String>,
+        /// Drop hostnames matching this regex (repeatable)
+        #[arg(long)]
+        exclude_regex: Vec<String>,
+        /// Rewrite addresses, FROM may be an IP, a CIDR block or a prefix (e.g. 10.1.0.0/16=127.0.0.1)
+        #[arg(long, value_name = "FROM=TO")]
+        rewrite: Vec<String>,
+        #[arg(long)]
+        strip_comments: bool,
+        #[arg(long)]
+        dedupe: bool,
+    },
+    /// Pin a checksum or require a minisign signature for a remote profile's sources
+    Verify {
+        /// Profile name
+        name: String,
+        /// Only this source URL (default: every source of the profile)
+        #[arg(long)]
+        url: Option<String>,
+        /// Expected hex SHA-256 of the downloaded content
+        #[arg(long)]
+        sha256: Option<String>,
+        /// minisign public key (the base64 line starting with RW)
+        #[arg(long)]
+        minisign_key: Option<String>,
+        /// Signature location, defaults to <url>.minisig
+        #[arg(long)]
+        signature_url: Option<String>,
+        /// Remove all verification settings instead
+        #[arg(long, conflicts_with_all = ["sha256", "minisign_key", "signature_url"])]
+        clear: bool,
+    },
+    /// Download a remote profile and show what its transform rules change, without saving
+    Preview {
+        /// Profile name
+        name: String,
+    },
+    /// Choose whether one failing source fails the whole refresh
+    Policy {
+        /// Profile name
+        name: String,
+        #[arg(value_enum)]
+        policy: remote::SourcePolicy,
+    },
+    /// Set the fallback URLs of a source, tried in order when it fails (none clears them)
+    Mirrors {
+        /// Profile name
+        name: String,
+        /// Source the mirrors belong to, required if the profile has several
+        #[arg(long)]
+        url: Option<String>,
+        mirrors: Vec<String>,
+    },
+    /// Show the last result of every source of a remote profile
+    Status {
+        /// Profile name
+        name: String,
+    },
+    /// Show the most recent successful refreshes of a remote profile
+    Log {
+        /// Profile name
+        name: String,
+        /// Number of entries to show
+        #[arg(long, short = 'n', default_value_t = 10)]
+        limit: usize,
+    },
+}
+
+#[derive(Subcommand)]
+enum ControlAction {
+    /// Serve the control API from the GUI and the daemon
+    Enable {
+        /// Fixed port instead of a random free one
+        #[arg(long)]
+        port: Option<u16>,
+    },
+    /// Stop serving the control API
+    Disable,
+}
+
+#[derive(Subcommand)]
+enum ProxyAction {
+    /// Use http_proxy / HTTPS_PROXY / ALL_PROXY / NO_PROXY from the environment (default)
+    Env,
+    /// Always connect directly
+    Off,
+    /// Use a fixed HTTP or SOCKS proxy, e.g. socks5://127.0.0.1:1080
+    Set {
+        url: String,
+        #[arg(long)]
+        username: Option<String>,
+        #[arg(long)]
+        password: Option<String>,
+        /// Comma separated hosts to reach directly
+        #[arg(long, value_delimiter = ',')]
+        no_proxy: Vec<String>,
+    },
+}
+
+#[derive(Clone, Copy, PartialEq, ValueEnum)]
+enum ExportFormat {
+    /// Hostly full backup
+    Json,
+    /// SwitchHosts v4 backup
+    Switchhosts,
+    /// Tar archive with a checksummed manifest and one hosts file per profile
+    Archive,
+}
+
+impl Cli {
+    /// Makes file arguments absolute, for commands run on behalf of another process.
+    fn resolve_paths(&mut self, dir: &Path) {
+        let resolve = |target: &mut String| {
+            let lower = target.to_lowercase();
+            let is_url = lower.starts_with("http://") || lower.starts_with("https://") || lower.starts_with("file://");
+            if !is_url && Path::new(target.as_str()).is_relative() {
+                *target = dir.join(target.as_str()).to_string_lossy().into_owned();
+            }
+        };
+        match &mut self.command {
+            Some(Commands::Export { target, .. })
+            | Some(Commands::Import { target, .. })
+            | Some(Commands::Migration { target }) => resolve(target),
+            _ => {}
+        }
+    }
+
+    /// Commands that don't change the store, safe to run next to a running instance.
+    fn is_read_only(&self) -> bool {
+        matches!(
+            &self.command,
+            Some(Commands::List)
+                | Some(Commands::Export { .. })
+                | Some(Commands::Control { action: None })
+                | Some(Commands::Proxy { action: None })
+                | Some(Commands::Remote { action: RemoteAction::Status { .. } | RemoteAction::Log { .. } | RemoteAction::Preview { .. } })
+        )
+    }
+}
+
+/// Runs read-only commands (and prints help or usage errors) without starting the app, so
+/// their output reaches the terminal even while another instance is running. Everything else
+/// returns `false` and goes through the app, which forwards it to the running instance.
+pub fn run_standalone() -> bool {
+    let args: Vec<String> = std::env::args().collect();
+    if args.len() <= 1 {
+        return false;
+    }
+    match Cli::try_parse_from(&args) {
+        Ok(cli) if !cli.is_read_only() => false,
+        _ => run_cli(None),
+    }
+}
+
+/// Runs a command forwarded by a second launch in the running instance and returns what it
+/// printed. Relative paths are resolved against the directory it was started in.
+pub fn run_forwarded(app: &AppHandle, args: Vec<String>, cwd: &str) -> ForwardedOutput {
+    match execute(Some(app), args, Some(Path::new(cwd))) {
+        Some(mut out) => {
+            let code = out.exit_code();
+            let mut captured = out.captured.take().unwrap_or_default();
+            captured.code = code;
+            captured
+        }
+        None => ForwardedOutput::default(),
     }
 }
 
 pub fn run_cli(app: Option<&AppHandle>) -> bool {
+    let Some(out) = execute(app, std::env::args().collect(), None) else {
+        return false;
+    };
+    // Non-zero exit for scripts
+    if out.exit_code() != 0 {
+        std::process::exit(out.exit_code());
+    }
+    true // Command executed, exit app
+}
+
+/// Runs the command in `args`, `None` if there is none and the GUI should start instead.
+/// `forwarded_from` is the working directory of the process that forwarded the command; its
+/// output is captured rather than printed.
+fn execute(app: Option<&AppHandle>, args: Vec<String>, forwarded_from: Option<&Path>) -> Option<Output> {
     // We need to parse args. 
-    // clap::Parser::parse() reads from std::env::args().
     // If tauri app is run, first arg is binary path. 
     // If we have no args (length 1), we return false to let GUI run.
-    let args: Vec<String> = std::env::args().collect();
     if args.len() <= 1 {
-        return false;
+        return None;
     }
 
-    #[cfg(windows)]
-    check_elevation();
-
     // Try parsing. If it fails (e.g. invalid command), clap usually prints help and exits.
     // However, if we just run `hostly.exe`, we want GUI.
     // We already checked len <= 1. 
     // But what if user runs `hostly.exe --random-flag`? Clap will error.
     // That's fine, we want CLI behavior if args are present.
 
-    let cli = match Cli::try_parse() {
-        Ok(c) => c,
+    let parsed = Cli::command()
+        .try_get_matches_from(&args)
+        .and_then(|matches| Ok((Cli::from_arg_matches(&matches)?, command_name(&matches))));
+    let (mut cli, name) = match parsed {
+        Ok(parsed) => parsed,
         Err(e) => {
             // If error is just help or version, print and exit.
             // If unknown command, print error and exit.
             // But we must distinguish if it's meant for Tauri?
             // Tauri doesn't really take args unless configured.
-            e.print().unwrap();
-            return true; // Exit app
+            let format = if wants_json(&args) && e.use_stderr() { OutputFormat::Json } else { OutputFormat::Text };
+            let mut out = Output::new(format, false, String::new());
+            if forwarded_from.is_some() {
+                out = out.capture();
+            }
+            let message = e.to_string();
+            let first = message.lines().next().unwrap_or_default().trim_start_matches("error: ").to_string();
+            if format == OutputFormat::Json {
+                out.error(first);
+                out.finish();
+                return Some(out);
+            }
+            if out.captured.is_some() {
+                if e.use_stderr() { out.eprint(message.trim_end()) } else { out.print(message.trim_end()) }
+            } else {
+                e.print().unwrap();
+            }
+            // Already printed in full, only counts towards the exit status
+            if e.use_stderr() {
+                out.errors.push(first);
+            }
+            return Some(out); // Exit app
         }
     };
+    if let Some(dir) = forwarded_from {
+        cli.resolve_paths(dir);
+    }
+    // No subcommand, run GUI
+    let command = cli.command.take()?;
+
+    // Diagnostics would break the JSON document
+    #[cfg(windows)]
+    if cli.output == OutputFormat::Text && !cli.quiet && forwarded_from.is_none() {
+        check_elevation();
+    }
+
+    // Process wide, so not for commands running inside the GUI
+    if cli.quiet && forwarded_from.is_none() {
+        crate::logging::set_quiet(true);
+    }
 
     let ctx = match app {
         Some(h) => storage::Context::Tauri(h),
         None => storage::Context::Headless,
     };
+    let mut out = Output::new(cli.output, cli.quiet, name);
+    if forwarded_from.is_some() {
+        out = out.capture();
+    }
+    run_command(ctx, app, command, &mut out);
+    out.finish();
+    Some(out)
+}
 
-    match cli.command {
-        Some(Commands::List) => {
+/// Subcommand path of a parsed command line, e.g. "remote status".
+fn command_name(matches: &clap::ArgMatches) -> String {
+    let mut names = Vec::new();
+    let mut current = matches;
+    while let Some((name, sub)) = current.subcommand() {
+        names.push(name);
+        current = sub;
+    }
+    names.join(" ")
+}
+
+/// Whether a command line that failed to parse asked for JSON output anyway.
+fn wants_json(args: &[String]) -> bool {
+    args.iter().any(|a| a == "--output=json") || args.windows(2).any(|w| w[0] == "--output" && w[1] == "json")
+}
+
+fn run_command(ctx: storage::Context, app: Option<&AppHandle>, command: Commands, out: &mut Output) {
+    match command {
+        Commands::List => {
+            let metadata = storage::load_config_internal(&ctx).map(|c| c.profiles).unwrap_or_default();
             match storage::list_profiles_internal(&ctx) {
                 Ok(profiles) => {
                     for p in profiles {
-                        println!("{} [{}]", p.name, if p.active { "ACTIVE" } else { "OFF" });
+                        out.info(format!("{} [{}]", p.name, if p.active { "ACTIVE" } else { "OFF" }));
+                        if let Some(status) = p.remote.as_ref().filter(|r| r.failure_count > 0) {
+                            out.info(format!("    refresh failed {} time(s): {}", status.failure_count, status.last_error.as_deref().unwrap_or("unknown error")));
+                            if let Some(next) = &status.next_attempt {
+                                out.info(format!("    next retry: {}", next));
+                            }
+                        }
+                        let meta = metadata.iter().find(|m| m.id == p.id);
+                        out.item(json!({
+                            "id": p.id,
+                            "name": p.name,
+                            "active": p.active,
+                            "folder": meta.and_then(|m| m.folder.clone()),
+                            "update_interval": meta.and_then(|m| m.update_interval),
+                            "remote": p.remote,
+                        }));
                     }
                 }
-                Err(e) => eprintln!("Error listing profiles: {}", e),
+                Err(e) => out.error(format!("Error listing profiles: {}", e)),
             }
         },
-        Some(Commands::Single) => {
-            if let Err(e) = storage::set_multi_select_internal(&ctx, false) {
-                eprintln!("Error setting single mode: {}", e);
+        Commands::Single | Commands::Multi => {
+            let multi = matches!(command, Commands::Multi);
+            if let Err(e) = storage::set_multi_select_internal(&ctx, multi) {
+                out.error(format!("Error setting {} mode: {}", if multi { "multi" } else { "single" }, e));
             } else {
-                 println!("Single selection mode enabled.");
-                 if let Err(e) = storage::apply_config_internal(&ctx) {
-                     eprintln!("Failed to apply hosts: {}", e);
-                 }
-
-            }
-        },
-        Some(Commands::Multi) => {
-             if let Err(e) = storage::set_multi_select_internal(&ctx, true) {
-                eprintln!("Error setting multi mode: {}", e);
-            } else {
-                 println!("Multi selection mode enabled.");
-                 if let Err(e) = storage::apply_config_internal(&ctx) {
-                     eprintln!("Failed to apply hosts: {}", e);
-                 }
-
+                out.info(format!("{} selection mode enabled.", if multi { "Multi" } else { "Single" }));
+                out.field("multi_select", multi);
+                apply(&ctx, out);
             }
         },
-        Some(Commands::Open { names, multi }) => {
+        Commands::Open { names, multi } => {
             if multi {
                 if let Err(e) = storage::set_multi_select_internal(&ctx, true) {
-                    eprintln!("Error enabling multi-mode: {}", e);
-                    return true;
+                    out.error(format!("Error enabling multi-mode: {}", e));
+                    return;
                 }
             }
 
             // Check mode
             let config = storage::load_config_internal(&ctx).unwrap_or_default();
             if !config.multi_select && names.len() > 1 {
-                eprintln!("Warning: Single select mode is active. Only the first profile '{}' will be activated.", names[0]);
-                eprintln!("Use --multi to enable multi-select mode automatically.");
+                out.warn(format!("Single select mode is active. Only the first profile '{}' will be activated. Use --multi to enable multi-select mode automatically.", names[0]));
             }
 
             for name in names {
-                if let Ok(Some(id)) = storage::find_profile_id_by_name_internal(&ctx, &name) {
-                    let current_profiles = storage::list_profiles_internal(&ctx).unwrap_or_default();
-                    let p = current_profiles.iter().find(|p| p.id == id);
-                    if let Some(prof) = p {
-                        if !prof.active {
-                             if let Err(e) = storage::toggle_profile_active_internal(&ctx, &id) {
-                                  eprintln!("Failed to open '{}': {}", name, e);
-                             } else {
-                                  println!("Opened '{}'", name);
-                             }
-                        } else {
-                             println!("'{}' is already active.", name);
-                        }
-                    }
-                } else {
-                     eprintln!("Profile '{}' not found.", name);
-                }
-            }
-            if let Err(e) = storage::apply_config_internal(&ctx) {
-                eprintln!("Failed to apply hosts: {}", e);
+                switch_profile(&ctx, out, &name, true);
             }
-
+            apply(&ctx, out);
         },
-        Some(Commands::Close { names }) => {
-             for name in names {
-                 if let Ok(Some(id)) = storage::find_profile_id_by_name_internal(&ctx, &name) {
-                      let current_profiles = storage::list_profiles_internal(&ctx).unwrap_or_default();
-                      if let Some(prof) = current_profiles.iter().find(|p| p.id == id) {
-                           if prof.active {
-                                if let Err(e) = storage::toggle_profile_active_internal(&ctx, &id) {
-                                    eprintln!("Failed to close '{}': {}", name, e);
-                                } else {
-                                    println!("Closed '{}'", name);
-                                }
-                           } else {
-                                println!("'{}' is already closed.", name);
-                           }
-                      }
-                 } else {
-                      eprintln!("Profile '{}' not found.", name);
-                 }
-             }
-             if let Err(e) = storage::apply_config_internal(&ctx) {
-                 eprintln!("Failed to apply hosts: {}", e);
-             }
-
+        Commands::Close { names } => {
+            for name in names {
+                switch_profile(&ctx, out, &name, false);
+            }
+            apply(&ctx, out);
         },
-        Some(Commands::Export { name, target }) => {
+        Commands::Export { name, target, format } => {
+            out.field("target", &target);
+            if name.is_some() && format != ExportFormat::Json {
+                out.warn("--format only applies to full backups; exporting the profile as plain hosts.");
+            }
             if let Some(n) = name {
                 // Export Single
+                out.field("format", "hosts");
                 if let Ok(Some(id)) = storage::find_profile_id_by_name_internal(&ctx, &n) {
                      let current_profiles = storage::list_profiles_internal(&ctx).unwrap_or_default();
                      if let Some(p) = current_profiles.iter().find(|p| p.id == id) {
+                          out.field("profile", json!({ "id": p.id, "name": p.name }));
                           if let Err(e) = fs::write(&target, &p.content) {
-                               eprintln!("Failed to write file: {}", e);
+                               out.error(format!("Failed to write file: {}", e));
                           } else {
-                               println!("Exported '{}' to '{}'", n, target);
+                               out.info(format!("Exported '{}' to '{}'", n, target));
                           }
                      }
                 } else {
-                     eprintln!("Profile '{}' not found.", n);
+                     out.error(format!("Profile '{}' not found.", n));
                 }
             } else {
                 // Export All
-                match storage::export_data_internal(&ctx) {
+                out.field("format", format.to_possible_value().map(|v| v.get_name().to_string()));
+                if format == ExportFormat::Archive {
+                    match archive::export_archive_internal(&ctx, &PathBuf::from(&target)) {
+                        Ok(_) => out.info(format!("Archive backup exported to '{}'", target)),
+                        Err(e) => out.error(format!("Export failed: {}", e)),
+                    }
+                    return;
+                }
+                let exported = match format {
+                    ExportFormat::Switchhosts => switchhosts::export_switchhosts_internal(&ctx).map(|export| {
+                        for warning in export.warnings {
+                            out.warn(warning);
+                        }
+                        export.json
+                    }),
+                    _ => storage::export_data_internal(&ctx),
+                };
+                match exported {
                      Ok(json) => {
                           if let Err(e) = fs::write(&target, json) {
-                               eprintln!("Failed to write export file: {}", e);
+                               out.error(format!("Failed to write export file: {}", e));
                           } else {
-                               println!("Full backup exported to '{}'", target);
+                               out.info(format!("Full backup exported to '{}'", target));
                           }
                      },
-                     Err(e) => eprintln!("Export failed: {}", e),
+                     Err(e) => out.error(format!("Export failed: {}", e)),
                 }
             }
         },
-     Some(Commands::Import { name, target, open, multi, single }) => {
+     Commands::Import { name, target, open, multi, single, strategy, dry_run } => {
+             out.field("target", &target);
              // Check if target is a remote URL
-             let is_remote = target.to_lowercase().starts_with("http://") || target.to_lowercase().starts_with("https://");
+             let lower = target.to_lowercase();
+             let is_remote = lower.starts_with("http://") || lower.starts_with("https://") || lower.starts_with("file://");
 
              if is_remote {
                  if let Some(n) = &name {
-                     println!("Importing remote profile '{}' from '{}'...", n, target);
+                     out.info(format!("Importing remote profile '{}' from '{}'...", n, target));
+                     out.field("kind", "remote");
                      match storage::create_profile_internal(
                          &ctx,
                          n.clone(),
@@ -273,55 +630,92 @@ pub fn run_cli(app: Option<&AppHandle>) -> bool {
                          Some(3600) // Default 1 hour interval
                      ) {
                          Ok(id) => {
-                             println!("Profile created (ID: {}). Downloading content...", id);
-                             if let Err(e) = storage::trigger_profile_update_internal(&ctx, &id) {
-                                 eprintln!("Warning: Failed to download content: {}", e);
+                             out.info(format!("Profile created (ID: {}). Downloading content...", id));
+                             out.field("profile", json!({ "id": id, "name": n }));
+                             let downloaded = remote::trigger_profile_update_internal(&ctx, &id);
+                             out.field("downloaded", downloaded.is_ok());
+                             if let Err(e) = downloaded {
+                                 out.warn(format!("Failed to download content: {}", e));
                              } else {
-                                 println!("Content downloaded.");
+                                 out.info("Content downloaded.");
                              }
                          },
                          Err(e) => {
-                             eprintln!("Failed to create remote profile: {}", e);
-                             return true;
+                             out.error(format!("Failed to create remote profile: {}", e));
+                             return;
                          }
                      }
                  } else {
-                     eprintln!("Error: --name is required when importing a remote URL.");
-                     return true;
+                     out.error("--name is required when importing a remote URL.");
+                     return;
                  }
              } else {
                  // Existing Header (File Import)
                  let path = PathBuf::from(&target);
                  if !path.exists() {
-                     eprintln!("Target file '{}' not found.", target);
-                     return true;
+                     out.error(format!("Target file '{}' not found.", target));
+                     return;
                  }
 
-                 let content = match fs::read_to_string(&path) {
-                     Ok(c) => c,
-                     Err(e) => {
-                          eprintln!("Failed to read file: {}", e);
-                          return true;
+                 if name.is_none() && target.to_lowercase().ends_with(".tar") {
+                     out.field("kind", "archive");
+                     match archive::import_archive_internal(&ctx, &path, strategy, dry_run) {
+                         Ok(report) => {
+                             report_import(out, &report);
+                             if dry_run {
+                                 out.info("Dry run: nothing was changed.");
+                                 return;
+                             }
+                             out.info(format!("Archive backup imported from '{}'.", target));
+                         },
+                         Err(e) => {
+                             out.error(format!("Failed to import archive backup: {}", e));
+                             return;
+                         }
                      }
-                 };
-
-                 if let Some(n) = &name {
-                      match storage::upsert_profile_internal(&ctx, n.clone(), content) {
-                           Ok(_) => println!("Imported profile '{}'.", n),
-                           Err(e) => eprintln!("Import failed: {}", e)
-                      }
                  } else {
-                      if target.to_lowercase().ends_with(".json") {
-                          match storage::import_data_internal(&ctx, content) {
-                              Ok(_) => println!("Global backup imported from '{}'.", target),
-                              Err(e) => eprintln!("Failed to import global backup: {}", e),
+                     let content = match fs::read_to_string(&path) {
+                         Ok(c) => c,
+                         Err(e) => {
+                              out.error(format!("Failed to read file: {}", e));
+                              return;
+                         }
+                     };
+
+                     if let Some(n) = &name {
+                          out.field("kind", "profile");
+                          match storage::upsert_profile_internal(&ctx, n.clone(), content) {
+                               Ok(id) => {
+                                   out.info(format!("Imported profile '{}'.", n));
+                                   out.field("profile", json!({ "id": id, "name": n }));
+                               }
+                               Err(e) => out.error(format!("Import failed: {}", e))
                           }
-                      } else {
-                           match storage::save_common_config_internal(&ctx, content) {
-                                Ok(_) => println!("Common config updated from '{}'.", target),
-                                Err(e) => eprintln!("Failed to save common config: {}", e)
-                           }
-                      }
+                     } else {
+                          if target.to_lowercase().ends_with(".json") {
+                              out.field("kind", "backup");
+                              match storage::import_data_internal(&ctx, content, strategy, dry_run) {
+                                  Ok(report) => {
+                                      report_import(out, &report);
+                                      if dry_run {
+                                          out.info("Dry run: nothing was changed.");
+                                          return;
+                                      }
+                                      out.info(format!("Global backup imported from '{}'.", target));
+                                  },
+                                  Err(e) => {
+                                      out.error(format!("Failed to import global backup: {}", e));
+                                      return;
+                                  }
+                              }
+                          } else {
+                               out.field("kind", "common");
+                               match storage::save_common_config_internal(&ctx, content) {
+                                    Ok(_) => out.info(format!("Common config updated from '{}'.", target)),
+                                    Err(e) => out.error(format!("Failed to save common config: {}", e))
+                               }
+                          }
+                     }
                  }
              }
              
@@ -339,11 +733,11 @@ pub fn run_cli(app: Option<&AppHandle>) -> bool {
 
              if profiles_to_open.len() > 1 || multi {
                   if let Err(e) = storage::set_multi_select_internal(&ctx, true) {
-                      eprintln!("Error enabling multi-select mode: {}", e);
+                      out.error(format!("Error enabling multi-select mode: {}", e));
                   }
              } else if single {
                   if let Err(e) = storage::set_multi_select_internal(&ctx, false) {
-                      eprintln!("Error enabling single-select mode: {}", e);
+                      out.error(format!("Error enabling single-select mode: {}", e));
                   }
              }
 
@@ -353,47 +747,640 @@ pub fn run_cli(app: Option<&AppHandle>) -> bool {
                       if let Some(p) = list.iter().find(|p| p.id == pid) {
                            if !p.active {
                                 let _ = storage::toggle_profile_active_internal(&ctx, &pid);
-                                println!("Profile '{}' activated.", p_name);
+                                out.info(format!("Profile '{}' activated.", p_name));
+                                out.result(&p_name, Some(&pid), "opened", None);
                            } else {
-                                println!("Profile '{}' is already active.", p_name);
+                                out.info(format!("Profile '{}' is already active.", p_name));
+                                out.result(&p_name, Some(&pid), "already_active", None);
                            }
                       }
                  } else {
-                      eprintln!("Warning: Cannot open profile '{}' (not found).", p_name);
+                      let message = format!("Cannot open profile '{}' (not found).", p_name);
+                      out.result(&p_name, None, "not_found", Some(&message));
+                      out.warn(message);
                  }
              }
-             if let Err(e) = storage::apply_config_internal(&ctx) {
-                 eprintln!("Failed to apply hosts: {}", e);
-             }
-
+             apply(&ctx, out);
         },
-        Some(Commands::Migration { target }) => {
+        Commands::Migration { target } => {
+             out.field("target", &target);
              let path = PathBuf::from(&target);
              if !path.exists() {
-                 eprintln!("Target file '{}' not found.", target);
-                 return true;
+                 out.error(format!("Target file '{}' not found.", target));
+                 return;
              }
 
              let content = match fs::read_to_string(&path) {
                  Ok(c) => c,
                  Err(e) => {
-                      eprintln!("Failed to read file: {}", e);
-                      return true;
+                      out.error(format!("Failed to read file: {}", e));
+                      return;
+                 }
+             };
+
+             match switchhosts::import_switchhosts_internal(&ctx, content) {
+                 Ok(report) => {
+                     out.info(format!("Successfully migrated {} profiles from SwitchHosts backup '{}'", report.imported.len(), target));
+                     for item in &report.imported {
+                         let folder = item.folder.as_deref().map(|f| format!("{}/", f)).unwrap_or_default();
+                         let remote = if item.remote { " (remote)" } else { "" };
+                         let active = if item.active { " [ACTIVE]" } else { "" };
+                         if item.renamed {
+                             out.info(format!("  + {}{}{}{} (renamed from '{}')", folder, item.name, remote, active, item.title));
+                         } else {
+                             out.info(format!("  + {}{}{}{}", folder, item.name, remote, active));
+                         }
+                     }
+                     for item in &report.skipped {
+                         out.info(format!("  - skipped '{}': {}", item.title, item.reason));
+                     }
+                     if report.common_restored {
+                         out.info("  Common config restored.");
+                     }
+                     out.field("report", &report);
+                     apply(&ctx, out);
+                 }
+                 Err(e) => {
+                     out.error(format!("Migration failed: {}", e));
+                     out.error("Please check if the file is a valid SwitchHosts JSON backup.");
+                 }
+             }
+        },
+        Commands::Adopt => {
+             match storage::adopt_hosts_internal(&ctx) {
+                 Ok(report) => {
+                     if report.common_restored {
+                         out.info("Common config recovered.");
+                     }
+                     for p in &report.profiles {
+                         if p.created {
+                             out.info(format!("Recovered profile '{}' [ACTIVE]", p.name));
+                         } else {
+                             out.info(format!("Overwrote existing profile '{}' [ACTIVE]", p.name));
+                         }
+                     }
+                     out.info(format!("Adopted {} profiles from the system hosts file.", report.profiles.len()));
+                     out.field("report", &report);
                  }
+                 Err(e) => out.error(format!("Adopt failed: {}", e)),
+             }
+        },
+        Commands::Git { action: GitAction::Pull } => {
+             match git_sync::git_pull_internal(&ctx) {
+                 Ok(report) => {
+                     for name in &report.updated {
+                         out.info(format!("Updated '{}'", name));
+                     }
+                     for name in &report.created {
+                         out.info(format!("Created '{}'", name));
+                     }
+                     for name in &report.missing {
+                         out.warn(format!("'{}' no longer exists upstream (kept locally)", name));
+                     }
+                     out.field("report", &report);
+                     apply(&ctx, out);
+                 }
+                 Err(e) => out.error(format!("Pull failed: {}", e)),
+             }
+        },
+        Commands::Git { action: GitAction::Push } => {
+             match git_sync::git_push_internal(&ctx) {
+                 Ok(_) => out.info("Pushed profile changes."),
+                 Err(e) => out.error(format!("Push failed: {}", e)),
+             }
+        },
+        Commands::Remote { action: RemoteAction::Format { name, format, url, sink } } => {
+             let profile = storage::load_config_internal(&ctx)
+                 .ok()
+                 .and_then(|c| c.profiles.into_iter().find(|p| p.name == name));
+             let Some(profile) = profile else {
+                 out.error(format!("Profile '{}' not found.", name));
+                 return;
+             };
+             let Some(urls) = profile.url.clone() else {
+                 out.error(format!("Profile '{}' is not a remote profile.", name));
+                 return;
+             };
+
+             let mut options = profile.remote_options.clone();
+             let targets: Vec<String> = match url {
+                 Some(u) => vec![u],
+                 None => urls.lines().map(|s| s.trim().to_string()).filter(|s| !s.is_empty()).collect(),
+             };
+             for target in targets {
+                 options.sources.entry(target).or_default().format = format;
+             }
+             if sink.is_some() {
+                 options.sink_ip = sink;
+             }
+             save_remote_options(&ctx, out, &profile, options, format!("Updated source format of '{}'. It applies from the next refresh.", name));
+        },
+        Commands::Remote { action: RemoteAction::Transform {
+            name, include_suffix, include_regex, exclude_suffix, exclude_regex, rewrite, strip_comments, dedupe
+        } } => {
+             let profile = storage::load_config_internal(&ctx)
+                 .ok()
+                 .and_then(|c| c.profiles.into_iter().find(|p| p.name == name && p.url.is_some()));
+             let Some(profile) = profile else {
+                 out.error(format!("Remote profile '{}' not found.", name));
+                 return;
+             };
+
+             let mut rewrite_ips = std::collections::BTreeMap::new();
+             for r in rewrite {
+                 let Some((from, to)) = r.split_once('=') else {
+                     out.error(format!("Invalid rewrite '{}', expected FROM=TO", r));
+                     return;
+                 };
+                 rewrite_ips.insert(from.trim().to_string(), to.trim().to_string());
+             }
+             let mut options = profile.remote_options.clone();
+             options.transform = TransformRules {
+                 include: include_suffix.into_iter().map(HostPattern::Suffix)
+                     .chain(include_regex.into_iter().map(HostPattern::Regex))
+                     .collect(),
+                 exclude: exclude_suffix.into_iter().map(HostPattern::Suffix)
+                     .chain(exclude_regex.into_iter().map(HostPattern::Regex))
+                     .collect(),
+                 rewrite_ips,
+                 strip_comments,
+                 dedupe,
+             };
+             save_remote_options(&ctx, out, &profile, options, format!("Updated transform rules of '{}'. They apply from the next refresh.", name));
+        },
+        Commands::Remote { action: RemoteAction::Verify { name, url, sha256, minisign_key, signature_url, clear } } => {
+             let profile = storage::load_config_internal(&ctx)
+                 .ok()
+                 .and_then(|c| c.profiles.into_iter().find(|p| p.name == name && p.url.is_some()));
+             let Some(profile) = profile else {
+                 out.error(format!("Remote profile '{}' not found.", name));
+                 return;
              };
 
-             if let Ok(count) = storage::import_switchhosts_internal(&ctx, content) {
-                 println!("Successfully migrated {} profiles from SwitchHosts backup '{}'", count, target);
-                 if let Err(e) = storage::apply_config_internal(&ctx) {
-                     eprintln!("Failed to apply hosts: {}", e);
+             let mut options = profile.remote_options.clone();
+             let targets: Vec<String> = match url {
+                 Some(u) => vec![u],
+                 None => profile.url.as_deref().unwrap_or("").lines().map(|s| s.trim().to_string()).filter(|s| !s.is_empty()).collect(),
+             };
+             for target in targets {
+                 let source = options.sources.entry(target).or_default();
+                 if clear {
+                     source.sha256 = None;
+                     source.minisign_key = None;
+                     source.signature_url = None;
+                 } else {
+                     source.sha256 = sha256.clone().or(source.sha256.take());
+                     source.minisign_key = minisign_key.clone().or(source.minisign_key.take());
+                     source.signature_url = signature_url.clone().or(source.signature_url.take());
+                 }
+             }
+             save_remote_options(&ctx, out, &profile, options, format!("Updated verification settings of '{}'.", name));
+        },
+        Commands::Remote { action: RemoteAction::Preview { name } } => {
+             let id = match storage::find_profile_id_by_name_internal(&ctx, &name) {
+                 Ok(Some(id)) => id,
+                 _ => {
+                     out.error(format!("Profile '{}' not found.", name));
+                     return;
+                 }
+             };
+             match remote::preview_remote_internal(&ctx, &id, None) {
+                 Ok(preview) => {
+                     let kept: std::collections::HashSet<&str> = preview.transformed.lines().collect();
+                     let original: std::collections::HashSet<&str> = preview.raw.lines().collect();
+                     let removed: Vec<&str> = preview.raw.lines().filter(|l| !kept.contains(l)).collect();
+                     let added: Vec<&str> = preview.transformed.lines().filter(|l| !original.contains(l)).collect();
+                     for line in &removed {
+                         out.info(format!("- {}", line));
+                     }
+                     for line in &added {
+                         out.info(format!("+ {}", line));
+                     }
+                     out.info(format!("{} entries downloaded, {} after transform rules.", preview.raw_entries, preview.transformed_entries));
+                     out.field("profile", json!({ "id": id, "name": name }));
+                     out.field("raw_entries", preview.raw_entries);
+                     out.field("transformed_entries", preview.transformed_entries);
+                     out.field("removed", &removed);
+                     out.field("added", &added);
+                 }
+                 Err(e) => out.error(format!("Preview failed: {}", e)),
+             }
+        },
+        Commands::Remote { action: RemoteAction::Policy { name, policy } } => {
+             let profile = storage::load_config_internal(&ctx)
+                 .ok()
+                 .and_then(|c| c.profiles.into_iter().find(|p| p.name == name && p.url.is_some()));
+             let Some(profile) = profile else {
+                 out.error(format!("Remote profile '{}' not found.", name));
+                 return;
+             };
+             let mut options = profile.remote_options.clone();
+             options.policy = policy;
+             save_remote_options(&ctx, out, &profile, options, format!("Updated source policy of '{}'.", name));
+        },
+        Commands::Remote { action: RemoteAction::Mirrors { name, url, mirrors } } => {
+             let profile = storage::load_config_internal(&ctx)
+                 .ok()
+                 .and_then(|c| c.profiles.into_iter().find(|p| p.name == name && p.url.is_some()));
+             let Some(profile) = profile else {
+                 out.error(format!("Remote profile '{}' not found.", name));
+                 return;
+             };
+             let sources: Vec<String> = profile.url.as_deref().unwrap_or("").lines().map(|s| s.trim().to_string()).filter(|s| !s.is_empty()).collect();
+             let target = match url {
+                 Some(u) if sources.contains(&u) => u,
+                 Some(u) => {
+                     out.error(format!("'{}' is not a source of '{}'.", u, name));
+                     return;
+                 }
+                 None if sources.len() == 1 => sources[0].clone(),
+                 None => {
+                     out.error(format!("'{}' has {} sources, pick one with --url.", name, sources.len()));
+                     return;
+                 }
+             };
+             let mut options = profile.remote_options.clone();
+             options.sources.entry(target).or_default().mirrors = mirrors;
+             save_remote_options(&ctx, out, &profile, options, format!("Updated mirrors of '{}'.", name));
+        },
+        Commands::Remote { action: RemoteAction::Status { name } } => {
+             let profile = storage::list_profiles_internal(&ctx)
+                 .ok()
+                 .and_then(|profiles| profiles.into_iter().find(|p| p.name == name));
+             let Some((id, status)) = profile.and_then(|p| Some((p.id, p.remote?))) else {
+                 out.error(format!("Remote profile '{}' not found.", name));
+                 return;
+             };
+             if status.sources.is_empty() {
+                 out.info(format!("'{}' has not been refreshed yet.", name));
+             }
+             for source in &status.sources {
+                 let state = match &source.last_error {
+                     Some(e) => format!("FAILED: {}", e),
+                     None => "OK".to_string(),
+                 };
+                 out.info(format!("{} [{}]", source.url, state));
+                 if let Some(code) = source.status {
+                     out.info(format!("    status: {}", code));
+                 }
+                 out.info(format!("    size: {} bytes", source.size));
+                 out.info(format!("    last success: {}", source.last_success.as_deref().unwrap_or("never")));
+                 if let Some(mirror) = &source.served_by {
+                     out.info(format!("    served by mirror: {}", mirror));
+                 }
+             }
+             out.field("profile", json!({ "id": id, "name": name }));
+             out.field("status", &status);
+        },
+        Commands::Remote { action: RemoteAction::Log { name, limit } } => {
+             let id = match storage::find_profile_id_by_name_internal(&ctx, &name) {
+                 Ok(Some(id)) => id,
+                 _ => {
+                     out.error(format!("Profile '{}' not found.", name));
+                     return;
+                 }
+             };
+             out.field("profile", json!({ "id": id, "name": name }));
+             match remote::get_update_log_internal(&ctx, &id) {
+                 Ok(entries) if entries.is_empty() => out.info(format!("No updates recorded for '{}'.", name)),
+                 Ok(entries) => {
+                     for entry in entries.iter().rev().take(limit) {
+                         if entry.content_changed {
+                             out.info(format!("{}  +{} -{} ~{}  ({} bytes)", entry.timestamp, entry.diff.added, entry.diff.removed, entry.diff.changed, entry.bytes));
+                         } else {
+                             out.info(format!("{}  unchanged  ({} bytes)", entry.timestamp, entry.bytes));
+                         }
+                         for source in &entry.sources {
+                             let status = source.status.map(|s| s.to_string()).unwrap_or_else(|| "file".to_string());
+                             out.info(format!("    [{}] {} ({} bytes)", status, source.url, source.bytes));
+                         }
+                         out.item(json!(entry));
+                     }
+                 }
+                 Err(e) => out.error(format!("Failed to read update log: {}", e)),
+             }
+        },
+        Commands::Proxy { action: None } => {
+             match storage::load_config_internal(&ctx) {
+                 Ok(config) => {
+                     let proxy = &config.proxy;
+                     let url = proxy.url.as_deref().map(remote::redact_url);
+                     match proxy.mode {
+                         ProxyMode::Env => out.info("Using the proxy from the environment (http_proxy / HTTPS_PROXY / NO_PROXY)."),
+                         ProxyMode::Off => out.info("Proxy disabled, connecting directly."),
+                         ProxyMode::Manual => {
+                             out.info(format!("Proxy: {}", url.clone().unwrap_or_default()));
+                             if let Some(user) = &proxy.username {
+                                 out.info(format!("User: {}", user));
+                             }
+                             if !proxy.no_proxy.is_empty() {
+                                 out.info(format!("Direct: {}", proxy.no_proxy.join(", ")));
+                             }
+                         }
+                     }
+                     // Never the password
+                     out.field("proxy", json!({
+                         "mode": proxy.mode,
+                         "url": url,
+                         "username": proxy.username,
+                         "no_proxy": proxy.no_proxy,
+                     }));
+                 }
+                 Err(e) => out.error(format!("Error loading config: {}", e)),
+             }
+        },
+        Commands::Proxy { action: Some(action) } => {
+             let settings = match action {
+                 ProxyAction::Env => ProxySettings { mode: ProxyMode::Env, ..Default::default() },
+                 ProxyAction::Off => ProxySettings { mode: ProxyMode::Off, ..Default::default() },
+                 ProxyAction::Set { url, username, password, no_proxy } => ProxySettings {
+                     mode: ProxyMode::Manual,
+                     url: Some(url),
+                     username,
+                     password,
+                     no_proxy,
+                 },
+             };
+             out.field("mode", settings.mode);
+             match proxy::set_proxy_internal(&ctx, settings) {
+                 Ok(_) => out.info("Proxy settings saved."),
+                 Err(e) => out.error(format!("Failed to save proxy settings: {}", e)),
+             }
+        },
+        Commands::Layout { layout } => {
+             out.field("layout", layout);
+             match storage::set_storage_layout_internal(&ctx, layout) {
+                 Ok(_) => out.info(format!("Profiles are now stored in the '{}' layout.", layout.to_possible_value().map(|v| v.get_name().to_string()).unwrap_or_default())),
+                 Err(e) => out.error(format!("Failed to change layout: {}", e)),
+             }
+        },
+        Commands::Control { action: None } => {
+             match control::control_status(&ctx) {
+                 Ok(status) => {
+                     if !status.settings.enabled {
+                         out.info("Control API is disabled.");
+                     } else {
+                         match status.settings.port {
+                             Some(port) => out.info(format!("Control API is enabled on port {}.", port)),
+                             None => out.info("Control API is enabled on a random port."),
+                         }
+                         out.info(format!("Address and token of the running endpoint: {}", status.endpoint_file));
+                     }
+                     out.field("control", &status);
+                 }
+                 Err(e) => out.error(format!("Error loading config: {}", e)),
+             }
+        },
+        Commands::Control { action: Some(action) } => {
+             let settings = match action {
+                 ControlAction::Enable { port } => ControlSettings { enabled: true, port },
+                 ControlAction::Disable => ControlSettings::default(),
+             };
+             out.field("settings", &settings);
+             match control::set_control_settings_internal(&ctx, settings) {
+                 Ok(_) => out.info("Control API settings saved, restart Hostly or the daemon to apply them."),
+                 Err(e) => out.error(format!("Failed to save control API settings: {}", e)),
+             }
+        },
+        Commands::Update { name, all } => {
+             let config = match storage::load_config_internal(&ctx) {
+                 Ok(c) => c,
+                 Err(e) => {
+                     out.error(format!("Error loading config: {}", e));
+                     return;
                  }
+             };
+             let ids: Vec<String> = match (&name, all) {
+                 (Some(name), _) => match config.profiles.iter().find(|p| &p.name == name) {
+                     Some(p) if p.url.is_some() => vec![p.id.clone()],
+                     Some(_) => {
+                         out.error(format!("Profile '{}' is not a remote profile.", name));
+                         return;
+                     }
+                     None => {
+                         out.error(format!("Profile '{}' not found.", name));
+                         return;
+                     }
+                 },
+                 (None, true) => config.profiles.iter().filter(|p| p.url.is_some()).map(|p| p.id.clone()).collect(),
+                 (None, false) => scheduler::due_profiles(&config),
+             };
+             if ids.is_empty() {
+                 out.info("No remote profiles to update.");
+                 return;
+             }
 
-             } else {
-                 eprintln!("Migration failed. Please check if the file is a valid SwitchHosts JSON backup.");
+             let names: std::collections::HashMap<&str, &str> = config.profiles.iter().map(|p| (p.id.as_str(), p.name.as_str())).collect();
+             for (id, result) in scheduler::refresh_now(&ctx, &ids) {
+                 let name = names.get(id.as_str()).copied().unwrap_or(id.as_str());
+                 match result {
+                     Ok(true) => {
+                         out.info(format!("Updated '{}'", name));
+                         out.result(name, Some(&id), "updated", None);
+                     }
+                     Ok(false) => {
+                         out.info(format!("'{}' is up to date", name));
+                         out.result(name, Some(&id), "unchanged", None);
+                     }
+                     Err(e) => {
+                         out.error(format!("Failed to update '{}': {}", name, e));
+                         out.result(name, Some(&id), "failed", Some(&e));
+                     }
+                 }
+             }
+        },
+        Commands::Daemon { pidfile, log_format } => {
+             if app.is_some() {
+                 out.error("The daemon runs without the GUI, use hostly-core daemon instead.");
+                 return;
+             }
+             if let Err(e) = daemon::run(DaemonOptions { pidfile, log_format }) {
+                 out.error(format!("Daemon failed: {}", e));
              }
         },
-        None => return false // No subcommand, run GUI
     }
+}
 
-    true // Command executed, exit app
+/// Switches one profile on or off by name for `open`/`close`.
+fn switch_profile(ctx: &storage::Context, out: &mut Output, name: &str, open: bool) {
+    let Ok(Some(id)) = storage::find_profile_id_by_name_internal(ctx, name) else {
+        let message = format!("Profile '{}' not found.", name);
+        out.result(name, None, "not_found", Some(&message));
+        out.error(message);
+        return;
+    };
+    let current_profiles = storage::list_profiles_internal(ctx).unwrap_or_default();
+    let Some(prof) = current_profiles.iter().find(|p| p.id == id) else {
+        return;
+    };
+    let (verb, done, unchanged) = if open { ("open", "opened", "already_active") } else { ("close", "closed", "already_closed") };
+    if prof.active == open {
+        out.info(if open { format!("'{}' is already active.", name) } else { format!("'{}' is already closed.", name) });
+        out.result(name, Some(&id), unchanged, None);
+    } else if let Err(e) = storage::toggle_profile_active_internal(ctx, &id) {
+        out.error(format!("Failed to {} '{}': {}", verb, name, e));
+        out.result(name, Some(&id), "failed", Some(&e));
+    } else {
+        out.info(format!("{} '{}'", if open { "Opened" } else { "Closed" }, name));
+        out.result(name, Some(&id), done, None);
+    }
+}
+
+fn save_remote_options(ctx: &storage::Context, out: &mut Output, profile: &storage::ProfileMetadata, options: remote::RemoteOptions, saved: String) {
+    out.field("profile", json!({ "id": profile.id, "name": profile.name }));
+    match remote::set_remote_options_internal(ctx, &profile.id, options) {
+        Ok(_) => out.info(saved),
+        Err(e) => out.error(format!("Failed to update '{}': {}", profile.name, e)),
+    }
+}
+
+fn apply(ctx: &storage::Context, out: &mut Output) {
+    if let Err(e) = storage::apply_config_internal(ctx) {
+        out.error(format!("Failed to apply hosts: {}", e));
+    }
+}
+
+fn report_import(out: &mut Output, report: &storage::ImportReport) {
+    for name in &report.added {
+        out.info(format!("  + {}", name));
+    }
+    for name in &report.overwritten {
+        out.info(format!("  ~ {}", name));
+    }
+    for r in &report.renamed {
+        out.info(format!("  + {} (renamed from '{}')", r.to, r.from));
+    }
+    for name in &report.skipped {
+        out.info(format!("  = {} (already exists, skipped)", name));
+    }
+    for name in &report.removed {
+        out.info(format!("  - {}", name));
+    }
+    for w in &report.warnings {
+        out.warn(w.clone());
+    }
+    out.field("report", report);
+}
+
+/// Where a command's output goes. Text is printed as it comes; JSON is collected and printed
+/// as one document by `finish`, so scripts get a single parseable value on stdout.
+struct Output {
+    format: OutputFormat,
+    quiet: bool,
+    command: String,
+    fields: serde_json::Map<String, Value>,
+    results: Vec<Value>,
+    warnings: Vec<String>,
+    /// Any entry means exit status 1; printed right away in text mode
+    errors: Vec<String>,
+    /// Collect what would be printed instead, for commands forwarded by a second launch
+    captured: Option<ForwardedOutput>,
+}
+
+/// What a forwarded command printed and its exit status, sent back to the launch that
+/// forwarded it.
+#[derive(Debug, Serialize, serde::Deserialize, Default)]
+pub struct ForwardedOutput {
+    pub stdout: String,
+    pub stderr: String,
+    pub code: i32,
+}
+
+impl Output {
+    fn new(format: OutputFormat, quiet: bool, command: String) -> Self {
+        Output {
+            format,
+            quiet,
+            command,
+            fields: serde_json::Map::new(),
+            results: Vec::new(),
+            warnings: Vec::new(),
+            errors: Vec::new(),
+            captured: None,
+        }
+    }
+
+    fn capture(mut self) -> Self {
+        self.captured = Some(ForwardedOutput::default());
+        self
+    }
+
+    fn print(&mut self, line: &str) {
+        match &mut self.captured {
+            Some(c) => {
+                c.stdout.push_str(line);
+                c.stdout.push('\n');
+            }
+            None => println!("{}", line),
+        }
+    }
+
+    fn eprint(&mut self, line: &str) {
+        match &mut self.captured {
+            Some(c) => {
+                c.stderr.push_str(line);
+                c.stderr.push('\n');
+            }
+            None => eprintln!("{}", line),
+        }
+    }
+
+    fn info(&mut self, message: impl Into<String>) {
+        if self.format == OutputFormat::Text && !self.quiet {
+            self.print(&message.into());
+        }
+    }
+
+    fn warn(&mut self, message: impl Into<String>) {
+        let message = message.into();
+        match self.format {
+            OutputFormat::Text => self.eprint(&format!("Warning: {}", message)),
+            OutputFormat::Json => self.warnings.push(message),
+        }
+    }
+
+    fn error(&mut self, message: impl Into<String>) {
+        let message = message.into();
+        if self.format == OutputFormat::Text {
+            self.eprint(&message);
+        }
+        self.errors.push(message);
+    }
+
+    /// Top-level field of the JSON document.
+    fn field(&mut self, key: &str, value: impl Serialize) {
+        if self.format == OutputFormat::Json {
+            self.fields.insert(key.to_string(), json!(value));
+        }
+    }
+
+    /// Entry of the `results` array.
+    fn item(&mut self, value: Value) {
+        if self.format == OutputFormat::Json {
+            self.results.push(value);
+        }
+    }
+
+    /// Outcome for one named profile, `status` is a fixed word like "opened" or "not_found".
+    fn result(&mut self, name: &str, id: Option<&str>, status: &str, error: Option<&str>) {
+        self.item(json!({ "name": name, "id": id, "status": status, "error": error }));
+    }
+
+    fn finish(&mut self) {
+        if self.format != OutputFormat::Json {
+            return;
+        }
+        let mut document = serde_json::Map::new();
+        document.insert("command".into(), json!(self.command));
+        document.insert("ok".into(), json!(self.errors.is_empty()));
+        document.append(&mut self.fields);
+        document.insert("results".into(), json!(self.results));
+        document.insert("warnings".into(), json!(self.warnings));
+        document.insert("errors".into(), json!(self.errors));
+        self.print(&serde_json::to_string_pretty(&Value::Object(document)).unwrap_or_default());
+    }
+
+    fn exit_code(&self) -> i32 {
+        if self.errors.is_empty() { 0 } else { 1 }
+    }
 }
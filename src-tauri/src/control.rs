@@ -0,0 +1,390 @@
+use crate::logging;
+use crate::scheduler::SchedulerContext;
+use crate::storage::{self, Context};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::fs;
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use tauri::AppHandle;
+
+/// Port and token of the running endpoint, next to config.json.
+const ENDPOINT_FILE: &str = "control.json";
+/// Requests carry a few names at most.
+const MAX_BODY_SIZE: u64 = 64 * 1024;
+
+/// Opt-in loopback HTTP endpoint for scripts and build tools.
+#[derive(Debug, Serialize, Deserialize, Clone, Default, PartialEq)]
+pub struct ControlSettings {
+    #[serde(default)]
+    pub enabled: bool,
+    /// Fixed port, a random free one if unset
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub port: Option<u16>,
+}
+
+/// Contents of `control.json` while the endpoint is up. Clients read the address and token
+/// from it; the token changes every time the endpoint starts. When both the GUI and the daemon
+/// serve the API, the file describes the one started last.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ControlEndpoint {
+    pub url: String,
+    pub port: u16,
+    pub token: String,
+    pub pid: u32,
+}
+
+#[derive(Debug, Serialize, Clone)]
+pub struct ControlStatus {
+    pub settings: ControlSettings,
+    /// Port the endpoint listens on in this process, `None` if it is not running
+    pub port: Option<u16>,
+    /// Where clients find the port and token
+    pub endpoint_file: String,
+}
+
+struct Running {
+    server: Arc<tiny_http::Server>,
+    port: u16,
+    thread: std::thread::JoinHandle<()>,
+}
+
+static SERVER: Mutex<Option<Running>> = Mutex::new(None);
+
+#[tauri::command]
+pub fn get_control_api(app: AppHandle) -> Result<ControlStatus, String> {
+    control_status(&Context::Tauri(&app))
+}
+
+#[tauri::command]
+pub fn set_control_api(app: AppHandle, settings: ControlSettings) -> Result<ControlStatus, String> {
+    let ctx = Context::Tauri(&app);
+    set_control_settings_internal(&ctx, settings)?;
+    start(SchedulerContext::Tauri(app.clone()))?;
+    control_status(&ctx)
+}
+
+pub fn set_control_settings_internal(ctx: &Context, settings: ControlSettings) -> Result<(), String> {
+    let mut config = storage::load_config_internal(ctx)?;
+    config.control_api = settings;
+    storage::save_config_internal(ctx, &config)
+}
+
+pub fn control_status(ctx: &Context) -> Result<ControlStatus, String> {
+    let config = storage::load_config_internal(ctx)?;
+    let port = SERVER.lock().unwrap_or_else(|e| e.into_inner()).as_ref().map(|r| r.port);
+    Ok(ControlStatus {
+        settings: config.control_api,
+        port,
+        endpoint_file: endpoint_path(ctx)?.display().to_string(),
+    })
+}
+
+fn endpoint_path(ctx: &Context) -> Result<PathBuf, String> {
+    Ok(ctx.get_app_dir()?.join(ENDPOINT_FILE))
+}
+
+/// Starts (or restarts) the endpoint according to the saved settings, stopping it when it
+/// is disabled. Requests are served one at a time on a background thread.
+pub fn start(ctx: SchedulerContext) -> Result<(), String> {
+    ctx.with(stop);
+    let settings = ctx.with(storage::load_config_internal)?.control_api;
+    if !settings.enabled {
+        return Ok(());
+    }
+
+    let server = tiny_http::Server::http(("127.0.0.1", settings.port.unwrap_or(0)))
+        .map_err(|e| format!("Failed to start control API: {}", e))?;
+    let port = server.server_addr().to_ip().map(|a| a.port()).ok_or("Control API has no TCP address")?;
+    let server = Arc::new(server);
+
+    let token = format!("{}{}", uuid::Uuid::new_v4().simple(), uuid::Uuid::new_v4().simple());
+    let endpoint = ControlEndpoint {
+        url: format!("http://127.0.0.1:{}", port),
+        port,
+        token: token.clone(),
+        pid: std::process::id(),
+    };
+    ctx.with(|c| write_endpoint_file(c, &endpoint))?;
+
+    logging::info(
+        &format!("Control API listening on {}", endpoint.url),
+        &[("event", "control_started"), ("url", &endpoint.url)],
+    );
+    let listener = server.clone();
+    let thread = std::thread::spawn(move || {
+        for mut request in listener.incoming_requests() {
+            let (code, body) = ctx.with(|c| handle(c, &token, port, &mut request, storage::apply_config_internal));
+            let response = tiny_http::Response::from_string(body.to_string())
+                .with_status_code(code)
+                .with_header(tiny_http::Header::from_bytes("Content-Type", "application/json").expect("static header"));
+            let _ = request.respond(response);
+        }
+    });
+    *SERVER.lock().unwrap_or_else(|e| e.into_inner()) = Some(Running { server, port, thread });
+    Ok(())
+}
+
+/// Stops the endpoint of this process, if running, and removes `control.json` unless another
+/// process (the GUI and the daemon share the data directory) has written its own since.
+pub fn stop(ctx: &Context) {
+    if let Some(running) = SERVER.lock().unwrap_or_else(|e| e.into_inner()).take() {
+        running.server.unblock();
+        // Wait for the port to be released, a restart may want it back
+        let _ = running.thread.join();
+        let Ok(path) = endpoint_path(ctx) else {
+            return;
+        };
+        let owner = fs::read_to_string(&path)
+            .ok()
+            .and_then(|c| serde_json::from_str::<ControlEndpoint>(&c).ok())
+            .map(|e| e.pid);
+        if owner == Some(std::process::id()) {
+            let _ = fs::remove_file(path);
+        }
+    }
+}
+
+fn write_endpoint_file(ctx: &Context, endpoint: &ControlEndpoint) -> Result<(), String> {
+    let content = serde_json::to_string_pretty(endpoint).map_err(|e| e.to_string())?;
+    write_private_file(&endpoint_path(ctx)?, &content)
+}
+
+/// Writes a file only the current user can read, for files holding a token.
+pub(crate) fn write_private_file(path: &Path, content: &str) -> Result<(), String> {
+    let mut options = fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    // Holds the token, keep it private to the user from the start
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+    let mut file = options.open(path).map_err(|e| format!("Failed to write {}: {}", path.display(), e))?;
+    file.write_all(content.as_bytes()).map_err(|e| format!("Failed to write {}: {}", path.display(), e))
+}
+
+#[derive(Deserialize)]
+struct ProfilesRequest {
+    /// Names or ids
+    profiles: Vec<String>,
+    /// Switch to multi-select mode first, like `hostly open --multi`
+    #[serde(default)]
+    multi: bool,
+}
+
+#[derive(Deserialize)]
+struct SceneRequest {
+    /// Name or id
+    scene: String,
+}
+
+type Apply = fn(&Context) -> Result<(), String>;
+
+fn error(code: u16, message: impl Into<String>) -> (u16, Value) {
+    (code, json!({ "error": message.into() }))
+}
+
+/// Answers one request. `apply` writes the hosts file, tests pass a stand-in.
+fn handle(ctx: &Context, token: &str, port: u16, request: &mut tiny_http::Request, apply: Apply) -> (u16, Value) {
+    let header = |name: &str| {
+        request.headers().iter()
+            .find(|h| h.field.as_str().as_str().eq_ignore_ascii_case(name))
+            .map(|h| h.value.as_str().to_string())
+    };
+    // Browsers always send Origin on cross-site requests, and a rebound DNS name shows up in
+    // Host; neither has any business here
+    if header("Origin").is_some() {
+        return error(403, "Cross-origin requests are not allowed");
+    }
+    let host_ok = header("Host").is_some_and(|h| h == format!("127.0.0.1:{}", port) || h == format!("localhost:{}", port));
+    if !host_ok {
+        return error(403, "Unexpected Host header");
+    }
+    let authorized = header("Authorization")
+        .and_then(|v| v.strip_prefix("Bearer ").map(|t| t.trim().to_string()))
+        .is_some_and(|t| constant_time_eq(t.as_bytes(), token.as_bytes()));
+    if !authorized {
+        return error(401, "Missing or wrong token, see control.json in the app data directory");
+    }
+
+    let mut body = String::new();
+    if request.as_reader().take(MAX_BODY_SIZE).read_to_string(&mut body).is_err() {
+        return error(400, "Request body is not valid UTF-8");
+    }
+    let method = request.method().as_str().to_ascii_uppercase();
+    let path = request.url().split('?').next().unwrap_or("").trim_end_matches('/').to_string();
+
+    let result = match (method.as_str(), path.as_str()) {
+        ("GET", "/v1/profiles") => list_profiles(ctx),
+        ("GET", "/v1/scenes") => list_scenes(ctx),
+        ("POST", "/v1/profiles/open") => parse::<ProfilesRequest>(&body).and_then(|r| switch_profiles(ctx, r, true, apply)),
+        ("POST", "/v1/profiles/close") => parse::<ProfilesRequest>(&body).and_then(|r| switch_profiles(ctx, r, false, apply)),
+        ("POST", "/v1/scenes/activate") => parse::<SceneRequest>(&body).and_then(|r| activate_scene(ctx, r, apply)),
+        ("POST", "/v1/apply") => apply(ctx).map_err(|e| (500, e)).and_then(|_| list_profiles(ctx)),
+        (_, "/v1/profiles" | "/v1/scenes" | "/v1/profiles/open" | "/v1/profiles/close" | "/v1/scenes/activate" | "/v1/apply") => {
+            Err((405, format!("{} is not allowed here", method)))
+        }
+        _ => Err((404, format!("Unknown endpoint {}", path))),
+    };
+
+    match result {
+        Ok(value) => (200, value),
+        Err((code, message)) => error(code, message),
+    }
+}
+
+fn parse<T: serde::de::DeserializeOwned>(body: &str) -> Result<T, (u16, String)> {
+    serde_json::from_str(body).map_err(|e| (400, format!("Invalid request body: {}", e)))
+}
+
+fn list_profiles(ctx: &Context) -> Result<Value, (u16, String)> {
+    let config = storage::load_config_internal(ctx).map_err(|e| (500, e))?;
+    let profiles: Vec<Value> = config.profiles.iter()
+        .map(|p| json!({
+            "id": p.id,
+            "name": p.name,
+            "active": p.active,
+            "folder": p.folder,
+            "remote": p.url.is_some(),
+        }))
+        .collect();
+    Ok(json!({ "multi_select": config.multi_select, "profiles": profiles }))
+}
+
+fn list_scenes(ctx: &Context) -> Result<Value, (u16, String)> {
+    let config = storage::load_config_internal(ctx).map_err(|e| (500, e))?;
+    Ok(json!({ "scenes": config.scenes }))
+}
+
+fn switch_profiles(ctx: &Context, request: ProfilesRequest, open: bool, apply: Apply) -> Result<Value, (u16, String)> {
+    let config = storage::load_config_internal(ctx).map_err(|e| (500, e))?;
+    // Resolve everything first so a typo doesn't leave half the profiles switched
+    let ids = request.profiles.iter()
+        .map(|r| {
+            config.profiles.iter()
+                .find(|p| &p.id == r || &p.name == r)
+                .map(|p| p.id.clone())
+                .ok_or((404, format!("Profile '{}' not found", r)))
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+
+    if open && request.multi {
+        storage::set_multi_select_internal(ctx, true).map_err(|e| (500, e))?;
+    }
+    for id in &ids {
+        let switched = if open {
+            storage::activate_profile_internal(ctx, id)
+        } else {
+            storage::deactivate_profile_internal(ctx, id)
+        };
+        switched.map_err(|e| (500, e))?;
+    }
+    apply(ctx).map_err(|e| (500, e))?;
+    list_profiles(ctx)
+}
+
+fn activate_scene(ctx: &Context, request: SceneRequest, apply: Apply) -> Result<Value, (u16, String)> {
+    let config = storage::load_config_internal(ctx).map_err(|e| (500, e))?;
+    let scene = config.scenes.iter()
+        .find(|s| s.id == request.scene || s.name == request.scene)
+        .ok_or((404, format!("Scene '{}' not found", request.scene)))?;
+    storage::activate_scene_internal(ctx, &scene.id).map_err(|e| (500, e))?;
+    apply(ctx).map_err(|e| (500, e))?;
+    list_profiles(ctx)
+}
+
+pub(crate) fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0u8, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tiny_http::{Header, Method, TestRequest};
+
+    const TOKEN: &str = "secret";
+    const PORT: u16 = 4321;
+
+    fn header(name: &str, value: &str) -> Header {
+        Header::from_bytes(name, value).unwrap()
+    }
+
+    /// A request from a script: right Host and token, no Origin.
+    fn request(method: Method, path: &str, body: &'static str) -> TestRequest {
+        TestRequest::new()
+            .with_method(method)
+            .with_path(path)
+            .with_body(body)
+            .with_header(header("Host", &format!("127.0.0.1:{}", PORT)))
+            .with_header(header("Authorization", &format!("Bearer {}", TOKEN)))
+    }
+
+    fn send(ctx: &Context, request: TestRequest) -> (u16, Value) {
+        handle(ctx, TOKEN, PORT, &mut request.into(), |_| Ok(()))
+    }
+
+    fn store(dir: &Path) -> Context<'static> {
+        let ctx = Context::Dir(dir.to_path_buf());
+        storage::save_config_internal(&ctx, &storage::AppConfig::default()).unwrap();
+        storage::create_profile_internal(&ctx, "Dev".into(), None, None, None).unwrap();
+        storage::create_profile_internal(&ctx, "Test".into(), None, None, None).unwrap();
+        ctx
+    }
+
+    #[test]
+    fn requests_need_the_token() {
+        let dir = tempfile::tempdir().unwrap();
+        let ctx = store(dir.path());
+        let anonymous = TestRequest::new().with_path("/v1/profiles").with_header(header("Host", &format!("127.0.0.1:{}", PORT)));
+        assert_eq!(send(&ctx, anonymous).0, 401);
+        let wrong = TestRequest::new()
+            .with_path("/v1/profiles")
+            .with_header(header("Host", &format!("localhost:{}", PORT)))
+            .with_header(header("Authorization", "Bearer secreT"));
+        assert_eq!(send(&ctx, wrong).0, 401);
+        assert_eq!(send(&ctx, request(Method::Get, "/v1/profiles", "")).0, 200);
+    }
+
+    #[test]
+    fn browsers_and_rebound_hosts_are_refused() {
+        let dir = tempfile::tempdir().unwrap();
+        let ctx = store(dir.path());
+        let cross_site = request(Method::Post, "/v1/apply", "").with_header(header("Origin", "https://evil.example"));
+        assert_eq!(send(&ctx, cross_site).0, 403);
+        let rebound = TestRequest::new()
+            .with_path("/v1/profiles")
+            .with_header(header("Host", &format!("evil.example:{}", PORT)))
+            .with_header(header("Authorization", &format!("Bearer {}", TOKEN)));
+        assert_eq!(send(&ctx, rebound).0, 403);
+        let other_port = TestRequest::new()
+            .with_path("/v1/profiles")
+            .with_header(header("Host", "127.0.0.1:80"))
+            .with_header(header("Authorization", &format!("Bearer {}", TOKEN)));
+        assert_eq!(send(&ctx, other_port).0, 403);
+    }
+
+    #[test]
+    fn routes() {
+        let dir = tempfile::tempdir().unwrap();
+        let ctx = store(dir.path());
+        assert_eq!(send(&ctx, request(Method::Get, "/v1/nope", "")).0, 404);
+        assert_eq!(send(&ctx, request(Method::Get, "/v1/profiles/open", "")).0, 405);
+        assert_eq!(send(&ctx, request(Method::Post, "/v1/profiles/open", "{")).0, 400);
+        assert_eq!(send(&ctx, request(Method::Post, "/v1/profiles/open", r#"{"profiles": ["Dev", "Typo"]}"#)).0, 404);
+
+        let (code, body) = send(&ctx, request(Method::Post, "/v1/profiles/open/", r#"{"profiles": ["Dev", "Test"], "multi": true}"#));
+        assert_eq!(code, 200);
+        assert_eq!(body["multi_select"], true);
+        let active: Vec<&str> = body["profiles"].as_array().unwrap().iter()
+            .filter(|p| p["active"] == true)
+            .map(|p| p["name"].as_str().unwrap())
+            .collect();
+        assert_eq!(active, ["Dev", "Test"]);
+
+        let (code, body) = send(&ctx, request(Method::Post, "/v1/profiles/close", r#"{"profiles": ["Test"]}"#));
+        assert_eq!(code, 200);
+        assert_eq!(body["profiles"][1]["active"], false);
+    }
+}
@@ -0,0 +1,140 @@
+use crate::control;
+use crate::logging::{self, LogFormat};
+use crate::scheduler::{self, SchedulerContext};
+use crate::storage::{self, Context};
+use std::fs;
+use std::path::PathBuf;
+use std::time::Duration;
+
+/// How long shutdown waits for refreshes that are still writing profile files.
+const SHUTDOWN_GRACE: Duration = Duration::from_secs(10);
+
+pub struct DaemonOptions {
+    pub pidfile: Option<PathBuf>,
+    pub log_format: LogFormat,
+}
+
+/// Runs the remote refresh scheduler and the file/network watchers in the foreground until
+/// SIGINT/SIGTERM (Ctrl+C on Windows). SIGHUP makes the scheduler re-read the config. Serves
+/// the control API too when it is enabled.
+pub fn run(options: DaemonOptions) -> Result<(), String> {
+    logging::set_format(options.log_format);
+
+    let ctx = Context::Headless;
+    let data_dir = ctx.get_app_dir()?.display().to_string();
+    // Fail early on a broken config instead of logging the same error every minute
+    storage::load_config_internal(&ctx)?;
+
+    let _pidfile = options.pidfile.map(Pidfile::create).transpose()?;
+
+    let runtime = tokio::runtime::Builder::new_multi_thread()
+        .enable_all()
+        .build()
+        .map_err(|e| format!("Failed to start runtime: {}", e))?;
+
+    let pid = std::process::id().to_string();
+    logging::info(
+        &format!("Hostly daemon started (pid {}, data in {})", pid, data_dir),
+        &[("event", "daemon_started"), ("pid", &pid), ("data_dir", &data_dir)],
+    );
+
+    if let Err(e) = control::start(SchedulerContext::Headless) {
+        logging::error(&e, &[("event", "control_failed")]);
+    }
+
+    let reason = runtime.block_on(async {
+        tokio::spawn(scheduler::run(SchedulerContext::Headless));
+        tokio::spawn(scheduler::watch(SchedulerContext::Headless));
+        wait_for_shutdown().await
+    })?;
+
+    logging::info(&format!("Received {}, shutting down...", reason), &[("event", "daemon_stopping"), ("signal", reason)]);
+    control::stop(&ctx);
+    runtime.shutdown_timeout(SHUTDOWN_GRACE);
+    logging::info("Hostly daemon stopped", &[("event", "daemon_stopped")]);
+    Ok(())
+}
+
+#[cfg(unix)]
+async fn wait_for_shutdown() -> Result<&'static str, String> {
+    use tokio::signal::unix::{signal, SignalKind};
+
+    let listen = |kind| signal(kind).map_err(|e| format!("Failed to install signal handler: {}", e));
+    let mut terminate = listen(SignalKind::terminate())?;
+    let mut interrupt = listen(SignalKind::interrupt())?;
+    let mut hangup = listen(SignalKind::hangup())?;
+    loop {
+        tokio::select! {
+            _ = terminate.recv() => return Ok("SIGTERM"),
+            _ = interrupt.recv() => return Ok("SIGINT"),
+            _ = hangup.recv() => {
+                logging::info("Received SIGHUP, reloading config", &[("event", "daemon_reload")]);
+                scheduler::wake();
+            }
+        }
+    }
+}
+
+#[cfg(not(unix))]
+async fn wait_for_shutdown() -> Result<&'static str, String> {
+    tokio::signal::ctrl_c()
+        .await
+        .map_err(|e| format!("Failed to install signal handler: {}", e))?;
+    Ok("Ctrl+C")
+}
+
+/// Holds the pidfile for the lifetime of the daemon and removes it on drop.
+struct Pidfile {
+    path: PathBuf,
+    pid: String,
+}
+
+impl Pidfile {
+    fn create(path: PathBuf) -> Result<Self, String> {
+        if let Ok(existing) = fs::read_to_string(&path) {
+            let existing = existing.trim();
+            if process_running(existing) {
+                return Err(format!("Another daemon is already running (pid {}, pidfile {})", existing, path.display()));
+            }
+            logging::warn(
+                &format!("Removing stale pidfile {}", path.display()),
+                &[("event", "stale_pidfile"), ("path", &path.display().to_string()), ("pid", existing)],
+            );
+        }
+        if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+            fs::create_dir_all(parent).map_err(|e| format!("Failed to create {}: {}", parent.display(), e))?;
+        }
+        let pid = std::process::id().to_string();
+        fs::write(&path, format!("{}\n", pid)).map_err(|e| format!("Failed to write pidfile {}: {}", path.display(), e))?;
+        Ok(Self { path, pid })
+    }
+}
+
+impl Drop for Pidfile {
+    fn drop(&mut self) {
+        // Leave it alone if another instance took it over in the meantime
+        if fs::read_to_string(&self.path).is_ok_and(|c| c.trim() == self.pid) {
+            let _ = fs::remove_file(&self.path);
+        }
+    }
+}
+
+/// Whether `pid` belongs to a live process. Only Linux can tell cheaply (`/proc`); elsewhere
+/// an existing pidfile is treated as stale, the service manager is expected to prevent
+/// double starts there.
+fn process_running(pid: &str) -> bool {
+    let Ok(pid) = pid.parse::<u32>() else {
+        return false;
+    };
+    if pid == std::process::id() {
+        return false;
+    }
+    #[cfg(target_os = "linux")]
+    {
+        std::path::Path::new("/proc").join(pid.to_string()).exists()
+    }
+    #[cfg(not(target_os = "linux"))]
+    {
+        false
+    }
+}
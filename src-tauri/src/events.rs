@@ -0,0 +1,124 @@
+use crate::remote::UpdateLogEntry;
+use crate::storage::{self, AppConfig, Context};
+use serde::Serialize;
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::SystemTime;
+use tauri::Emitter;
+
+/// State changes pushed to the frontend, so it stays current when the CLI, the control API or
+/// the scheduler change something behind its back. Each variant is sent as its own event
+/// (see `name`), with the fields as payload.
+#[derive(Debug, Clone, Serialize)]
+#[serde(untagged)]
+pub enum AppEvent {
+    /// A profile file was written, or the profile was renamed, moved or deleted
+    ProfileUpdated { id: String, deleted: bool },
+    /// The set of active profiles or the selection mode changed
+    ProfileActivated { active: Vec<String>, multi_select: bool },
+    /// The merged hosts file was written
+    ApplySucceeded { active: Vec<String> },
+    ApplyFailed { error: String },
+    RemoteRefreshStarted { id: String },
+    /// Sent after every refresh attempt; `entry` is set when it succeeded, `error` when not
+    RemoteRefreshFinished {
+        id: String,
+        changed: bool,
+        /// One line, ready for a notification; only set when the content changed
+        summary: Option<String>,
+        entry: Option<UpdateLogEntry>,
+        error: Option<String>,
+    },
+    /// The system hosts file no longer holds what was last written or seen
+    HostsFileDrifted { path: String },
+}
+
+impl AppEvent {
+    pub fn name(&self) -> &'static str {
+        match self {
+            AppEvent::ProfileUpdated { .. } => "profile-updated",
+            AppEvent::ProfileActivated { .. } => "profile-activated",
+            AppEvent::ApplySucceeded { .. } => "apply-succeeded",
+            AppEvent::ApplyFailed { .. } => "apply-failed",
+            AppEvent::RemoteRefreshStarted { .. } => "remote-refresh-started",
+            AppEvent::RemoteRefreshFinished { .. } => "remote-refresh-finished",
+            AppEvent::HostsFileDrifted { .. } => "hosts-file-drifted",
+        }
+    }
+}
+
+/// Sends `event` to the windows of the app. Headless runs (CLI, daemon) have nobody to tell;
+/// the GUI notices what they changed through `check_external_changes`.
+pub fn emit(ctx: &Context, event: AppEvent) {
+    if let Context::Tauri(app) = ctx {
+        let _ = app.emit(event.name(), event);
+    }
+}
+
+/// What the GUI last saw of the store: the selection, and per profile its settings (minus the
+/// on/off state) and the modification time of its file.
+#[derive(PartialEq)]
+struct Snapshot {
+    active: Vec<String>,
+    multi_select: bool,
+    profiles: HashMap<String, (String, Option<SystemTime>)>,
+}
+
+static KNOWN: Mutex<Option<Snapshot>> = Mutex::new(None);
+
+fn snapshot(ctx: &Context, config: &AppConfig) -> Snapshot {
+    let profiles = config.profiles.iter()
+        .map(|p| {
+            let settings = serde_json::to_string(&storage::ProfileMetadata { active: false, ..p.clone() }).unwrap_or_default();
+            let mtime = storage::profile_file_path(ctx, config, &p.id).ok()
+                .and_then(|path| std::fs::metadata(path).and_then(|m| m.modified()).ok());
+            (p.id.clone(), (settings, mtime))
+        })
+        .collect();
+    Snapshot {
+        active: config.profiles.iter().filter(|p| p.active).map(|p| p.id.clone()).collect(),
+        multi_select: config.multi_select,
+        profiles,
+    }
+}
+
+/// Records a write made by the GUI itself, which already sent its events.
+pub fn note_written(ctx: &Context, config: &AppConfig) {
+    if let Context::Tauri(_) = ctx {
+        *KNOWN.lock().unwrap_or_else(|e| e.into_inner()) = Some(snapshot(ctx, config));
+    }
+}
+
+/// Sends the events for changes other processes (the CLI, the daemon) made to the store since
+/// the last check. Their refreshes and applies show up as `ProfileUpdated` and
+/// `HostsFileDrifted` only, the details stay in their own logs.
+pub fn check_external_changes(ctx: &Context) -> Result<(), String> {
+    if let Context::Headless = ctx {
+        return Ok(());
+    }
+    let config = storage::load_config_internal(ctx)?;
+    let current = snapshot(ctx, &config);
+    let mut known = KNOWN.lock().unwrap_or_else(|e| e.into_inner());
+    let mut events = Vec::new();
+    // The first check only records the baseline
+    if let Some(previous) = known.as_ref().filter(|previous| **previous != current) {
+        for (id, state) in &current.profiles {
+            if previous.profiles.get(id) != Some(state) {
+                events.push(AppEvent::ProfileUpdated { id: id.clone(), deleted: false });
+            }
+        }
+        for id in previous.profiles.keys().filter(|id| !current.profiles.contains_key(*id)) {
+            events.push(AppEvent::ProfileUpdated { id: id.clone(), deleted: true });
+        }
+        if previous.active != current.active || previous.multi_select != current.multi_select {
+            events.push(AppEvent::ProfileActivated { active: current.active.clone(), multi_select: current.multi_select });
+        }
+    }
+    *known = Some(current);
+    drop(known);
+
+    for event in events {
+        emit(ctx, event);
+    }
+    Ok(())
+}
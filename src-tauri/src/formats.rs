@@ -0,0 +1,140 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::net::IpAddr;
+
+/// Format a remote source is published in; everything is converted to hosts entries.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Default, clap::ValueEnum)]
+#[serde(rename_all = "snake_case")]
+pub enum SourceFormat {
+    /// `IP hostname...` lines, used as is
+    #[default]
+    Hosts,
+    /// One domain per line
+    DomainList,
+    /// Basic Adblock rules (`||example.com^`), everything else is ignored
+    AdblockBasic,
+    /// dnsmasq `address=/example.com/0.0.0.0` lines
+    Dnsmasq,
+}
+
+pub const DEFAULT_SINK_IP: &str = "0.0.0.0";
+
+fn is_valid_hostname(name: &str) -> bool {
+    !name.is_empty()
+        && name.len() <= 253
+        && !name.starts_with(['.', '-'])
+        && !name.ends_with('.')
+        && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '.' || c == '_')
+}
+
+/// Converts `content` from `format` into hosts entries pointing at `sink`. Lines that aren't
+/// understood are dropped; hosts content is returned unchanged.
+pub fn to_hosts(content: &str, format: SourceFormat, sink: IpAddr) -> String {
+    if format == SourceFormat::Hosts {
+        return content.to_string();
+    }
+
+    let mut seen = HashSet::new();
+    let mut out = String::new();
+    let mut push = |ip: IpAddr, domain: &str| {
+        let domain = domain.to_ascii_lowercase();
+        if is_valid_hostname(&domain) && seen.insert(domain.clone()) {
+            out.push_str(&format!("{} {}\n", ip, domain));
+        }
+    };
+
+    for line in content.lines() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with(['#', '!']) {
+            continue;
+        }
+        match format {
+            SourceFormat::Hosts => {}
+            SourceFormat::DomainList => {
+                let mut parts = line.split_whitespace();
+                let first = parts.next().unwrap_or("");
+                // Tolerate lists that are secretly hosts files
+                let domain = if first.parse::<IpAddr>().is_ok() { parts.next().unwrap_or("") } else { first };
+                push(sink, domain.split('#').next().unwrap_or(""));
+            }
+            SourceFormat::AdblockBasic => {
+                // Exceptions and cosmetic rules can't be expressed in a hosts file
+                if line.starts_with("@@") || line.starts_with('[') || line.contains("##") || line.contains("#@#") {
+                    continue;
+                }
+                let Some(rule) = line.strip_prefix("||") else {
+                    continue;
+                };
+                let (pattern, options) = rule.split_once('$').unwrap_or((rule, ""));
+                // Only whole-domain blocks; options other than these narrow the rule down
+                if !options.split(',').all(|o| matches!(o.trim(), "" | "important" | "all" | "doc" | "document")) {
+                    continue;
+                }
+                if let Some(domain) = pattern.strip_suffix('^').or(Some(pattern).filter(|p| !p.contains(['/', '*', '^']))) {
+                    push(sink, domain);
+                }
+            }
+            SourceFormat::Dnsmasq => {
+                let Some(rule) = line.strip_prefix("address=/") else {
+                    continue;
+                };
+                let mut parts: Vec<&str> = rule.split('/').collect();
+                let target = parts.pop().unwrap_or("").trim();
+                // Blocking targets (empty, `#`, unspecified or loopback) go to the sink, real
+                // addresses are kept
+                let ip = match target.parse::<IpAddr>() {
+                    Ok(ip) if !ip.is_unspecified() && !ip.is_loopback() => ip,
+                    _ => sink,
+                };
+                for domain in parts {
+                    push(ip, domain);
+                }
+            }
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sink() -> IpAddr {
+        DEFAULT_SINK_IP.parse().unwrap()
+    }
+
+    #[test]
+    fn hosts_is_passed_through() {
+        let content = "# comment\n1.2.3.4 a.test b.test\n";
+        assert_eq!(to_hosts(content, SourceFormat::Hosts, sink()), content);
+    }
+
+    #[test]
+    fn domain_list() {
+        let content = "# list\nAds.Example.com\n0.0.0.0 tracker.test # inline\nbad!domain\n-bad.test\nads.example.com\n";
+        assert_eq!(
+            to_hosts(content, SourceFormat::DomainList, sink()),
+            "0.0.0.0 ads.example.com\n0.0.0.0 tracker.test\n"
+        );
+    }
+
+    #[test]
+    fn adblock_basic_keeps_only_whole_domain_blocks() {
+        let content = "[Adblock Plus 2.0]\n! comment\n||ads.test^\n||doc.test^$document,important\n||third.test^$third-party\n\
+                       @@||allowed.test^\nexample.com##.banner\n||path.test/ads\n||wild*.test^\n|http://x.test\n||bare.test\n";
+        assert_eq!(
+            to_hosts(content, SourceFormat::AdblockBasic, sink()),
+            "0.0.0.0 ads.test\n0.0.0.0 doc.test\n0.0.0.0 bare.test\n"
+        );
+    }
+
+    #[test]
+    fn dnsmasq_blocks_go_to_the_sink() {
+        let content = "address=/a.test/0.0.0.0\naddress=/b.test/c.test/\naddress=/d.test/127.0.0.1\n\
+                       address=/e.test/10.0.0.1\nserver=/f.test/8.8.8.8\n";
+        assert_eq!(
+            to_hosts(content, SourceFormat::Dnsmasq, "::".parse().unwrap()),
+            ":: a.test\n:: b.test\n:: c.test\n:: d.test\n10.0.0.1 e.test\n"
+        );
+    }
+}
@@ -0,0 +1,594 @@
+use crate::logging;
+use crate::storage::{self, AppConfig, Context, ProfileMetadata};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+use tauri::AppHandle;
+use uuid::Uuid;
+
+const PROFILE_EXT: &str = "hosts";
+/// Clone, pull and push talk to the remote; one that stalls must not block the caller forever.
+const GIT_TIMEOUT: Duration = Duration::from_secs(120);
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct GitSyncConfig {
+    /// Local git working tree the profiles are mirrored into
+    pub repo_dir: String,
+    /// Only profiles in this folder (and below) are synced; `None` syncs the whole store
+    #[serde(default)]
+    pub folder: Option<String>,
+    #[serde(default = "default_remote")]
+    pub remote: String,
+    /// Branch to pull/push, defaults to the currently checked out one
+    #[serde(default)]
+    pub branch: Option<String>,
+}
+
+fn default_remote() -> String {
+    "origin".to_string()
+}
+
+#[derive(Debug, Serialize, Clone, Default)]
+pub struct GitPullReport {
+    pub updated: Vec<String>,
+    pub created: Vec<String>,
+    /// Synced profiles whose file no longer exists upstream; they are kept locally
+    pub missing: Vec<String>,
+}
+
+fn git(repo: &Path, args: &[&str]) -> Result<String, String> {
+    let mut child = Command::new("git")
+        .arg("-C")
+        .arg(repo)
+        .args(args)
+        // Fail instead of waiting for a password nobody can type
+        .env("GIT_TERMINAL_PROMPT", "0")
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| format!("Failed to run git: {}", e))?;
+    let stdout = read_pipe(child.stdout.take());
+    let stderr = read_pipe(child.stderr.take());
+
+    let deadline = Instant::now() + GIT_TIMEOUT;
+    let status = loop {
+        if let Some(status) = child.try_wait().map_err(|e| format!("Failed to run git: {}", e))? {
+            break status;
+        }
+        if Instant::now() >= deadline {
+            let _ = child.kill();
+            let _ = child.wait();
+            return Err(format!("git {} timed out after {} seconds", args.first().unwrap_or(&""), GIT_TIMEOUT.as_secs()));
+        }
+        std::thread::sleep(Duration::from_millis(10));
+    };
+
+    let stdout = stdout.join().unwrap_or_default();
+    if status.success() {
+        Ok(stdout)
+    } else {
+        let stderr = stderr.join().unwrap_or_default();
+        Err(format!("git {} failed: {}", args.first().unwrap_or(&""), if stderr.trim().is_empty() { stdout.trim() } else { stderr.trim() }))
+    }
+}
+
+/// Drains a pipe of the child on its own thread, a full pipe would stall git.
+fn read_pipe<R: Read + Send + 'static>(pipe: Option<R>) -> JoinHandle<String> {
+    std::thread::spawn(move || {
+        let mut out = Vec::new();
+        if let Some(mut pipe) = pipe {
+            let _ = pipe.read_to_end(&mut out);
+        }
+        String::from_utf8_lossy(&out).to_string()
+    })
+}
+
+/// Values that end up on a git command line; one starting with '-' would be read as an option.
+fn check_git_arg(what: &str, value: &str) -> Result<(), String> {
+    if value.starts_with('-') {
+        return Err(format!("Invalid {} '{}': must not start with '-'", what, value));
+    }
+    Ok(())
+}
+
+/// Fallback identity for commits (including pull merges), so a machine without `user.email`
+/// configured can still sync.
+fn identity_args(repo: &Path) -> Vec<&'static str> {
+    if git(repo, &["config", "user.email"]).is_ok() {
+        Vec::new()
+    } else {
+        vec!["-c", "user.name=Hostly", "-c", "user.email=hostly@localhost"]
+    }
+}
+
+fn git_commit(repo: &Path, message: &str, paths: &[&str]) -> Result<(), String> {
+    // `diff --quiet` succeeds when there is nothing staged for these paths
+    let mut diff_args = vec!["diff", "--cached", "--quiet", "--"];
+    diff_args.extend(paths);
+    if git(repo, &diff_args).is_ok() {
+        return Ok(());
+    }
+
+    let mut args = identity_args(repo);
+    args.extend(["commit", "-m", message, "--"]);
+    args.extend(paths);
+    git(repo, &args).map(|_| ())
+}
+
+fn is_in_scope(sync: &GitSyncConfig, p: &ProfileMetadata) -> bool {
+    // Remote profiles are regenerated from their URL, committing them would only add noise
+    if p.url.is_some() {
+        return false;
+    }
+    match sync.folder.as_deref().map(storage::normalize_folder) {
+        None => true,
+        Some(root) if root.is_empty() => true,
+        Some(root) => {
+            let folder = p.folder.as_deref().map(storage::normalize_folder).unwrap_or_default();
+            folder == root || folder.starts_with(&format!("{}/", root))
+        }
+    }
+}
+
+/// Path of a profile relative to the working tree: the profile folder (minus the synced root)
+/// plus `<name>.hosts`.
+fn relative_path(sync: &GitSyncConfig, p: &ProfileMetadata) -> String {
+    let root = sync.folder.as_deref().map(storage::normalize_folder).unwrap_or_default();
+    let folder = p.folder.as_deref().map(storage::normalize_folder).unwrap_or_default();
+    let sub = folder.strip_prefix(&root).unwrap_or(&folder).trim_start_matches('/');
+    let file = format!("{}.{}", storage::profile_file_stem(&p.name), PROFILE_EXT);
+    if sub.is_empty() {
+        file
+    } else {
+        format!("{}/{}", sub, file)
+    }
+}
+
+/// The remote and branch come from config.json, which may have been edited by hand.
+fn check_sync_args(sync: &GitSyncConfig) -> Result<(), String> {
+    check_git_arg("remote", &sync.remote)?;
+    match &sync.branch {
+        Some(branch) => check_git_arg("branch", branch),
+        None => Ok(()),
+    }
+}
+
+fn sync_config(ctx: &Context) -> Result<Option<(AppConfig, GitSyncConfig)>, String> {
+    let config = storage::load_config_internal(ctx)?;
+    Ok(config.git_sync.clone().map(|sync| (config, sync)))
+}
+
+/// Mirrors a saved profile into the working tree and commits it.
+pub fn commit_profile(ctx: &Context, id: &str, message: Option<&str>) -> Result<(), String> {
+    let Some((config, sync)) = sync_config(ctx)? else {
+        return Ok(());
+    };
+    let Some(p) = config.profiles.iter().find(|p| p.id == id).filter(|p| is_in_scope(&sync, p)) else {
+        return Ok(());
+    };
+
+    let repo = PathBuf::from(&sync.repo_dir);
+    let rel = relative_path(&sync, p);
+    write_tree_file(&repo, &rel, &storage::read_profile_file_internal(ctx, id)?)?;
+    git(&repo, &["add", "--", &rel])?;
+    let default_message = format!("Update profile '{}'", p.name);
+    git_commit(&repo, message.unwrap_or(&default_message), &[&rel])
+}
+
+/// Moves or removes the file of a profile whose name/folder changed or that was deleted.
+/// `after` is `None` for deletions.
+pub fn move_profile(ctx: &Context, before: &ProfileMetadata, after: Option<&ProfileMetadata>) -> Result<(), String> {
+    let Some((_, sync)) = sync_config(ctx)? else {
+        return Ok(());
+    };
+
+    let repo = PathBuf::from(&sync.repo_dir);
+    let old_rel = is_in_scope(&sync, before).then(|| relative_path(&sync, before));
+    let new_rel = after.filter(|p| is_in_scope(&sync, p)).map(|p| relative_path(&sync, p));
+    if old_rel == new_rel {
+        return Ok(());
+    }
+
+    let mut paths: Vec<String> = Vec::new();
+    if let Some(old) = old_rel.filter(|rel| repo.join(rel).exists()) {
+        git(&repo, &["rm", "-q", "--", &old])?;
+        paths.push(old);
+    }
+    if let (Some(new), Some(p)) = (new_rel, after) {
+        write_tree_file(&repo, &new, &storage::read_profile_file_internal(ctx, &p.id)?)?;
+        git(&repo, &["add", "--", &new])?;
+        paths.push(new);
+    }
+    if paths.is_empty() {
+        return Ok(());
+    }
+
+    let message = match after {
+        Some(p) if p.name != before.name => format!("Rename profile '{}' to '{}'", before.name, p.name),
+        Some(p) => format!("Move profile '{}'", p.name),
+        None => format!("Delete profile '{}'", before.name),
+    };
+    let paths: Vec<&str> = paths.iter().map(|s| s.as_str()).collect();
+    git_commit(&repo, &message, &paths)
+}
+
+fn write_tree_file(repo: &Path, rel: &str, content: &str) -> Result<(), String> {
+    let path = repo.join(rel);
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent).map_err(|e| e.to_string())?;
+    }
+    fs::write(path, content).map_err(|e| e.to_string())
+}
+
+/// Collects `*.hosts` files below `dir`, returned relative to `root` with '/' separators.
+fn collect_tree_files(root: &Path, dir: &Path, files: &mut Vec<String>) -> Result<(), String> {
+    for entry in fs::read_dir(dir).map_err(|e| e.to_string())? {
+        let path = entry.map_err(|e| e.to_string())?.path();
+        if path.file_name().and_then(|n| n.to_str()) == Some(".git") {
+            continue;
+        }
+        if path.is_dir() {
+            collect_tree_files(root, &path, files)?;
+        } else if path.extension().and_then(|e| e.to_str()) == Some(PROFILE_EXT) {
+            if let Ok(rel) = path.strip_prefix(root) {
+                let rel: Vec<String> = rel.components().map(|c| c.as_os_str().to_string_lossy().to_string()).collect();
+                files.push(rel.join("/"));
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Commits synced profiles whose store content differs from their file in the working tree.
+/// Not every write path (CLI, control API, imports, edits picked up by the file watcher) goes
+/// through `commit_profile`; without this a pull would overwrite those edits instead of
+/// merging them. Files that are gone from the tree are left alone, see `GitPullReport::missing`.
+fn commit_local_changes(ctx: &Context, sync: &GitSyncConfig) -> Result<(), String> {
+    let repo = PathBuf::from(&sync.repo_dir);
+    let config = storage::load_config_internal(ctx)?;
+    let mut changed: Vec<String> = Vec::new();
+    for p in config.profiles.iter().filter(|p| is_in_scope(sync, p)) {
+        let rel = relative_path(sync, p);
+        let Ok(tree) = fs::read_to_string(repo.join(&rel)) else {
+            continue;
+        };
+        let content = storage::read_profile_file_internal(ctx, &p.id)?;
+        if tree != content {
+            write_tree_file(&repo, &rel, &content)?;
+            git(&repo, &["add", "--", &rel])?;
+            changed.push(rel);
+        }
+    }
+    if changed.is_empty() {
+        return Ok(());
+    }
+    let paths: Vec<&str> = changed.iter().map(|s| s.as_str()).collect();
+    git_commit(&repo, "Update profiles from Hostly", &paths)
+}
+
+/// Brings every `*.hosts` file of the working tree into the store: matching profiles get the
+/// file content, unknown files become new profiles.
+fn import_tree(ctx: &Context, sync: &GitSyncConfig) -> Result<GitPullReport, String> {
+    let repo = PathBuf::from(&sync.repo_dir);
+    let mut files = Vec::new();
+    collect_tree_files(&repo, &repo, &mut files)?;
+
+    let mut config = storage::load_config_internal(ctx)?;
+    let mut report = GitPullReport::default();
+    let root = sync.folder.as_deref().map(storage::normalize_folder).unwrap_or_default();
+
+    for rel in &files {
+        let content = fs::read_to_string(repo.join(rel)).map_err(|e| e.to_string())?;
+        let existing = config.profiles.iter().find(|p| is_in_scope(sync, p) && relative_path(sync, p) == *rel);
+        match existing {
+            Some(p) => {
+                if storage::read_profile_file_internal(ctx, &p.id)? != content {
+                    storage::save_profile_file_internal(ctx, &p.id, &content)?;
+                    report.updated.push(p.name.clone());
+                }
+            }
+            None => {
+                let (sub, file) = rel.rsplit_once('/').unwrap_or(("", rel));
+                let base = file.strip_suffix(&format!(".{}", PROFILE_EXT)).unwrap_or(file).to_string();
+                let folder = storage::normalize_folder(&format!("{}/{}", root, sub));
+                // A local-only profile (e.g. remote) may already use this name. Renaming the new
+                // profile would detach it from the file and create another one on every pull.
+                if let Some(other) = config.profiles.iter().find(|p| storage::same_file_name(&p.name, &base)) {
+                    logging::warn(
+                        &format!("Skipping '{}' from the git tree, its name collides with profile '{}'", rel, other.name),
+                        &[("event", "git_file_skipped"), ("path", rel)],
+                    );
+                    continue;
+                }
+                let name = base;
+                let id = Uuid::new_v4().to_string();
+                config.profiles.push(ProfileMetadata {
+                    id: id.clone(),
+                    name: name.clone(),
+                    folder: (!folder.is_empty()).then_some(folder),
+                    ..Default::default()
+                });
+                storage::write_profile_file(ctx, &config, &id, &content)?;
+                report.created.push(name);
+            }
+        }
+    }
+
+    for p in config.profiles.iter().filter(|p| is_in_scope(sync, p)) {
+        if !files.contains(&relative_path(sync, p)) && !report.created.contains(&p.name) {
+            report.missing.push(p.name.clone());
+        }
+    }
+
+    storage::save_config_internal(ctx, &config)?;
+    Ok(report)
+}
+
+#[tauri::command]
+pub fn enable_git_sync(
+    app: AppHandle,
+    repo_dir: String,
+    remote_url: Option<String>,
+    folder: Option<String>,
+    branch: Option<String>
+) -> Result<GitPullReport, String> {
+    enable_git_sync_internal(&Context::Tauri(&app), repo_dir, remote_url, folder, branch)
+}
+
+/// Uses `repo_dir` as the working tree, cloning `remote_url` into it (or running `git init`)
+/// when it is not a repository yet. Files already in the tree are imported first, then every
+/// synced profile that has no file yet is committed.
+pub fn enable_git_sync_internal(
+    ctx: &Context,
+    repo_dir: String,
+    remote_url: Option<String>,
+    folder: Option<String>,
+    branch: Option<String>
+) -> Result<GitPullReport, String> {
+    if let Some(url) = &remote_url {
+        check_git_arg("remote URL", url)?;
+    }
+    if let Some(branch) = &branch {
+        check_git_arg("branch", branch)?;
+    }
+    let repo = PathBuf::from(&repo_dir);
+    if !repo.join(".git").exists() {
+        match &remote_url {
+            Some(url) => {
+                if let Some(parent) = repo.parent().filter(|p| !p.as_os_str().is_empty()) {
+                    fs::create_dir_all(parent).map_err(|e| e.to_string())?;
+                }
+                git(Path::new("."), &["clone", "-q", "--", url, &repo_dir])?;
+            }
+            None => {
+                fs::create_dir_all(&repo).map_err(|e| e.to_string())?;
+                git(&repo, &["init", "-q"])?;
+            }
+        }
+    }
+
+    let sync = GitSyncConfig {
+        repo_dir,
+        folder: folder.map(|f| storage::normalize_folder(&f)).filter(|f| !f.is_empty()),
+        remote: default_remote(),
+        branch,
+    };
+    let mut config = storage::load_config_internal(ctx)?;
+    config.git_sync = Some(sync.clone());
+    storage::save_config_internal(ctx, &config)?;
+
+    let mut report = import_tree(ctx, &sync)?;
+    // Profiles without a file are exported below rather than reported as missing
+    report.missing.clear();
+
+    let config = storage::load_config_internal(ctx)?;
+    let mut added: Vec<String> = Vec::new();
+    for p in config.profiles.iter().filter(|p| is_in_scope(&sync, p)) {
+        let rel = relative_path(&sync, p);
+        if !repo.join(&rel).exists() {
+            write_tree_file(&repo, &rel, &storage::read_profile_file_internal(ctx, &p.id)?)?;
+            git(&repo, &["add", "--", &rel])?;
+            added.push(rel);
+        }
+    }
+    if !added.is_empty() {
+        let paths: Vec<&str> = added.iter().map(|s| s.as_str()).collect();
+        git_commit(&repo, "Add profiles from Hostly", &paths)?;
+    }
+
+    Ok(report)
+}
+
+#[tauri::command]
+pub fn disable_git_sync(app: AppHandle) -> Result<(), String> {
+    disable_git_sync_internal(&Context::Tauri(&app))
+}
+
+/// Stops mirroring; the working tree and its history are left untouched.
+pub fn disable_git_sync_internal(ctx: &Context) -> Result<(), String> {
+    let mut config = storage::load_config_internal(ctx)?;
+    config.git_sync = None;
+    storage::save_config_internal(ctx, &config)
+}
+
+#[tauri::command]
+pub fn git_pull(app: AppHandle) -> Result<GitPullReport, String> {
+    let ctx = Context::Tauri(&app);
+    let report = git_pull_internal(&ctx)?;
+    if !report.updated.is_empty() {
+        storage::apply_config(app)?;
+    }
+    Ok(report)
+}
+
+/// Commits pending local edits, merges the remote branch into the working tree and imports the
+/// result. On conflicts the merge is aborted and the conflicting files are reported, nothing in
+/// the store changes.
+pub fn git_pull_internal(ctx: &Context) -> Result<GitPullReport, String> {
+    let (_, sync) = sync_config(ctx)?.ok_or("Git sync is not enabled")?;
+    let repo = PathBuf::from(&sync.repo_dir);
+    check_sync_args(&sync)?;
+    // Local edits become a commit of their own, so the pull merges them (or reports a conflict)
+    commit_local_changes(ctx, &sync)?;
+
+    let mut args = identity_args(&repo);
+    args.extend(["pull", "--no-rebase", "--no-edit", sync.remote.as_str()]);
+    if let Some(branch) = &sync.branch {
+        args.push(branch);
+    }
+    if let Err(e) = git(&repo, &args) {
+        let conflicts = git(&repo, &["diff", "--name-only", "--diff-filter=U"]).unwrap_or_default();
+        let conflicts: Vec<&str> = conflicts.lines().filter(|l| !l.is_empty()).collect();
+        if conflicts.is_empty() {
+            return Err(e);
+        }
+        let _ = git(&repo, &["merge", "--abort"]);
+        return Err(format!(
+            "Pull aborted, conflicting changes in: {}. Resolve them in '{}' and pull again.",
+            conflicts.join(", "),
+            sync.repo_dir
+        ));
+    }
+
+    import_tree(ctx, &sync)
+}
+
+#[tauri::command]
+pub fn git_push(app: AppHandle) -> Result<(), String> {
+    git_push_internal(&Context::Tauri(&app))
+}
+
+pub fn git_push_internal(ctx: &Context) -> Result<(), String> {
+    let (_, sync) = sync_config(ctx)?.ok_or("Git sync is not enabled")?;
+    let repo = PathBuf::from(&sync.repo_dir);
+    check_sync_args(&sync)?;
+    commit_local_changes(ctx, &sync)?;
+
+    let branch = match &sync.branch {
+        Some(b) => b.clone(),
+        None => git(&repo, &["rev-parse", "--abbrev-ref", "HEAD"])?.trim().to_string(),
+    };
+    git(&repo, &["push", &sync.remote, &branch]).map(|_| ()).map_err(|e| {
+        if e.contains("rejected") || e.contains("non-fast-forward") {
+            "Push rejected because the remote has new commits, pull first".to_string()
+        } else {
+            e
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn run(dir: &Path, args: &[&str]) -> String {
+        let mut all = vec!["-c", "user.name=Test", "-c", "user.email=test@localhost"];
+        all.extend(args);
+        git(dir, &all).unwrap()
+    }
+
+    fn store(dir: &Path) -> Context<'static> {
+        let ctx = Context::Dir(dir.join("store"));
+        storage::save_config_internal(&ctx, &AppConfig::default()).unwrap();
+        ctx
+    }
+
+    /// A bare remote plus a second clone of it standing in for another machine.
+    fn remote(dir: &Path) -> (String, PathBuf) {
+        let url = dir.join("remote.git").display().to_string();
+        run(dir, &["init", "-q", "--bare", &url]);
+        let other = dir.join("other");
+        run(dir, &["clone", "-q", "--", &url, &other.display().to_string()]);
+        fs::write(other.join("shared.hosts"), "10.0.0.1 shared\n").unwrap();
+        run(&other, &["add", "--", "shared.hosts"]);
+        run(&other, &["commit", "-q", "-m", "Add shared"]);
+        run(&other, &["push", "-q", "origin", "HEAD"]);
+        (url, other)
+    }
+
+    fn names(config: &AppConfig) -> Vec<String> {
+        config.profiles.iter().map(|p| p.name.clone()).collect()
+    }
+
+    #[test]
+    fn enable_clones_imports_and_exports() {
+        let dir = tempfile::tempdir().unwrap();
+        let (url, _) = remote(dir.path());
+        let ctx = store(dir.path());
+        storage::create_profile_internal(&ctx, "Local".into(), Some("127.0.0.1 local\n".into()), None, None).unwrap();
+
+        let repo = dir.path().join("repo");
+        let report = enable_git_sync_internal(&ctx, repo.display().to_string(), Some(url), None, None).unwrap();
+        assert_eq!(report.created, ["shared"]);
+        assert!(report.missing.is_empty());
+
+        let config = storage::load_config_internal(&ctx).unwrap();
+        assert_eq!(names(&config), ["Local", "shared"]);
+        let shared = config.profiles.iter().find(|p| p.name == "shared").unwrap();
+        assert_eq!(storage::read_profile_file_internal(&ctx, &shared.id).unwrap(), "10.0.0.1 shared\n");
+        assert_eq!(run(&repo, &["show", "HEAD:Local.hosts"]), "127.0.0.1 local\n");
+    }
+
+    #[test]
+    fn pull_reports_created_updated_and_missing() {
+        let dir = tempfile::tempdir().unwrap();
+        let (url, other) = remote(dir.path());
+        let ctx = store(dir.path());
+        storage::create_profile_internal(&ctx, "Local".into(), Some("127.0.0.1 local\n".into()), None, None).unwrap();
+        let repo = dir.path().join("repo");
+        enable_git_sync_internal(&ctx, repo.display().to_string(), Some(url), None, None).unwrap();
+        git_push_internal(&ctx).unwrap();
+
+        run(&other, &["pull", "-q", "--no-rebase", "origin", "HEAD"]);
+        fs::write(other.join("shared.hosts"), "10.0.0.2 shared\n").unwrap();
+        fs::create_dir(other.join("team")).unwrap();
+        fs::write(other.join("team/new.hosts"), "10.0.0.3 new\n").unwrap();
+        run(&other, &["rm", "-q", "--", "Local.hosts"]);
+        run(&other, &["add", "--", "shared.hosts", "team/new.hosts"]);
+        run(&other, &["commit", "-q", "-m", "Upstream changes"]);
+        run(&other, &["push", "-q", "origin", "HEAD"]);
+
+        let report = git_pull_internal(&ctx).unwrap();
+        assert_eq!(report.updated, ["shared"]);
+        assert_eq!(report.created, ["new"]);
+        assert_eq!(report.missing, ["Local"]);
+
+        let config = storage::load_config_internal(&ctx).unwrap();
+        let new = config.profiles.iter().find(|p| p.name == "new").unwrap();
+        assert_eq!(new.folder.as_deref(), Some("team"));
+        let shared = config.profiles.iter().find(|p| p.name == "shared").unwrap();
+        assert_eq!(storage::read_profile_file_internal(&ctx, &shared.id).unwrap(), "10.0.0.2 shared\n");
+    }
+
+    #[test]
+    fn push_commits_edits_made_outside_the_sync() {
+        let dir = tempfile::tempdir().unwrap();
+        let (url, other) = remote(dir.path());
+        let ctx = store(dir.path());
+        let repo = dir.path().join("repo");
+        enable_git_sync_internal(&ctx, repo.display().to_string(), Some(url), None, None).unwrap();
+
+        let id = storage::find_profile_id_by_name_internal(&ctx, "shared").unwrap().unwrap();
+        storage::save_profile_file_internal(&ctx, &id, "10.0.0.9 shared\n").unwrap();
+        git_push_internal(&ctx).unwrap();
+
+        run(&other, &["pull", "-q", "--no-rebase", "origin", "HEAD"]);
+        assert_eq!(fs::read_to_string(other.join("shared.hosts")).unwrap(), "10.0.0.9 shared\n");
+    }
+
+    #[test]
+    fn option_like_urls_are_rejected() {
+        let dir = tempfile::tempdir().unwrap();
+        let ctx = store(dir.path());
+        let repo = dir.path().join("repo").display().to_string();
+        let err = enable_git_sync_internal(&ctx, repo.clone(), Some("--upload-pack=touch pwned".into()), None, None).unwrap_err();
+        assert!(err.contains("must not start with '-'"));
+        assert!(enable_git_sync_internal(&ctx, repo, None, None, Some("-f".into())).is_err());
+        assert!(storage::load_config_internal(&ctx).unwrap().git_sync.is_none());
+    }
+}
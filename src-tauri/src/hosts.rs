@@ -1,5 +1,10 @@
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::{BTreeSet, HashMap};
 use std::fs;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::SystemTime;
 
 #[cfg(target_os = "windows")]
 fn get_hosts_path() -> PathBuf {
@@ -11,6 +16,142 @@ fn get_hosts_path() -> PathBuf {
     PathBuf::from("/etc/hosts")
 }
 
+pub const GENERATED_HEADER: &str = "# Generated by Hostly";
+pub const COMMON_MARKER: &str = "### Common Config ###";
+const PROFILE_MARKER_PREFIX: &str = "### Profile: ";
+const MARKER_SUFFIX: &str = " ###";
+
+pub fn profile_marker(name: &str) -> String {
+    format!("{}{}{}", PROFILE_MARKER_PREFIX, name, MARKER_SUFFIX)
+}
+
+/// The hosts file written by `apply_config_internal`: the common config, then one section per
+/// (profile name, content), each followed by a blank line.
+pub fn generate_hosts(common: &str, profiles: &[(String, String)]) -> String {
+    let mut content = format!("{}\n\n", GENERATED_HEADER);
+    content.push_str(&format!("{}\n", COMMON_MARKER));
+    content.push_str(common);
+    content.push_str("\n\n");
+    for (name, body) in profiles {
+        content.push_str(&format!("{}\n", profile_marker(name)));
+        content.push_str(body);
+        content.push_str("\n\n");
+    }
+    content
+}
+
+/// Sections of a hosts file written by `apply_config_internal`.
+#[derive(Debug, Default)]
+pub struct GeneratedSections {
+    pub common: Option<String>,
+    /// (profile name, content) in file order
+    pub profiles: Vec<(String, String)>,
+}
+
+/// Splits a Hostly-generated hosts file back into its common config and profile sections.
+/// Returns `None` if the content carries no Hostly section markers.
+pub fn parse_generated_hosts(content: &str) -> Option<GeneratedSections> {
+    enum Section {
+        Preamble,
+        Common,
+        Profile(String),
+    }
+
+    let mut sections = GeneratedSections::default();
+    let mut current = Section::Preamble;
+    let mut body = String::new();
+    let mut found = false;
+
+    // Every section body is followed by a blank line on write, strip it again on read
+    let finish = |section: Section, body: &str, sections: &mut GeneratedSections| {
+        let body = body.strip_suffix("\r\n\r\n").or_else(|| body.strip_suffix("\n\n")).unwrap_or(body).to_string();
+        match section {
+            Section::Preamble => {}
+            Section::Common => sections.common = Some(body),
+            Section::Profile(name) => sections.profiles.push((name, body)),
+        }
+    };
+
+    for line in content.split_inclusive('\n') {
+        let marker = line.trim_end_matches(['\r', '\n']);
+        let next = if marker == COMMON_MARKER {
+            Some(Section::Common)
+        } else {
+            marker.strip_prefix(PROFILE_MARKER_PREFIX)
+                .and_then(|rest| rest.strip_suffix(MARKER_SUFFIX))
+                .map(|name| Section::Profile(name.to_string()))
+        };
+
+        match next {
+            Some(section) => {
+                finish(std::mem::replace(&mut current, section), &body, &mut sections);
+                body.clear();
+                found = true;
+            }
+            None => body.push_str(line),
+        }
+    }
+    finish(current, &body, &mut sections);
+
+    found.then_some(sections)
+}
+
+/// Counts `IP hostname...` lines and lines that are neither that nor a comment.
+pub fn count_entries(content: &str) -> (usize, usize) {
+    let mut entries = 0;
+    let mut invalid = 0;
+    for line in content.lines() {
+        let line = line.split('#').next().unwrap_or("").trim();
+        if line.is_empty() {
+            continue;
+        }
+        let mut parts = line.split_whitespace();
+        let is_entry = parts.next().is_some_and(|ip| ip.parse::<std::net::IpAddr>().is_ok()) && parts.next().is_some();
+        if is_entry {
+            entries += 1;
+        } else {
+            invalid += 1;
+        }
+    }
+    (entries, invalid)
+}
+
+/// Hostnames added, removed and pointed at different addresses between two hosts files.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct EntryDiff {
+    pub added: usize,
+    pub removed: usize,
+    pub changed: usize,
+}
+
+pub fn diff_entries(old: &str, new: &str) -> EntryDiff {
+    fn addresses(content: &str) -> HashMap<String, BTreeSet<String>> {
+        let mut map: HashMap<String, BTreeSet<String>> = HashMap::new();
+        for line in content.lines() {
+            let mut parts = line.split('#').next().unwrap_or("").split_whitespace();
+            let Some(ip) = parts.next().filter(|ip| ip.parse::<std::net::IpAddr>().is_ok()) else {
+                continue;
+            };
+            for host in parts {
+                map.entry(host.to_ascii_lowercase()).or_default().insert(ip.to_string());
+            }
+        }
+        map
+    }
+
+    let (old, new) = (addresses(old), addresses(new));
+    let mut diff = EntryDiff::default();
+    for (host, ips) in &new {
+        match old.get(host) {
+            None => diff.added += 1,
+            Some(previous) if previous != ips => diff.changed += 1,
+            Some(_) => {}
+        }
+    }
+    diff.removed = old.keys().filter(|host| !new.contains_key(*host)).count();
+    diff
+}
+
 #[tauri::command]
 pub fn get_system_hosts() -> Result<String, String> {
     let path = get_hosts_path();
@@ -20,9 +161,11 @@ pub fn get_system_hosts() -> Result<String, String> {
 #[tauri::command]
 pub fn save_system_hosts(content: String) -> Result<(), String> {
     let path = get_hosts_path();
+    // Held across the write so a drift check in between can't mistake it for an outside edit
+    let mut known = KNOWN_HOSTS.lock().unwrap_or_else(|e| e.into_inner());
     
     // Attempt normal write first
-    match fs::write(&path, &content) {
+    let result = match fs::write(&path, &content) {
         Ok(_) => Ok(()),
         Err(e) => {
             #[cfg(target_os = "macos")]
@@ -43,7 +186,44 @@ pub fn save_system_hosts(content: String) -> Result<(), String> {
             #[cfg(not(target_os = "macos"))]
             Err(e.to_string())
         }
+    };
+    if result.is_ok() {
+        *known = Some(KnownHosts::of(&path, content.as_bytes()));
+    }
+    result
+}
+
+/// The hosts file as Hostly last wrote or saw it. `None` until the first drift check, which
+/// only records the baseline.
+static KNOWN_HOSTS: Mutex<Option<KnownHosts>> = Mutex::new(None);
+
+struct KnownHosts {
+    modified: Option<SystemTime>,
+    digest: [u8; 32],
+}
+
+impl KnownHosts {
+    fn of(path: &Path, content: &[u8]) -> Self {
+        let modified = fs::metadata(path).and_then(|m| m.modified()).ok();
+        KnownHosts { modified, digest: Sha256::digest(content).into() }
+    }
+}
+
+/// Compares the hosts file with what Hostly last wrote or saw and returns its path if someone
+/// else changed it since. Each change is reported once. Only reads the file when its
+/// modification time moved.
+pub fn check_drift() -> Result<Option<String>, String> {
+    let path = get_hosts_path();
+    let modified = fs::metadata(&path).and_then(|m| m.modified()).ok();
+    let mut known = KNOWN_HOSTS.lock().unwrap_or_else(|e| e.into_inner());
+    if known.as_ref().is_some_and(|k| modified.is_some() && k.modified == modified) {
+        return Ok(None);
     }
+
+    let current = KnownHosts::of(&path, &fs::read(&path).map_err(|e| e.to_string())?);
+    let drifted = known.as_ref().is_some_and(|k| k.digest != current.digest);
+    *known = Some(current);
+    Ok(drifted.then(|| path.display().to_string()))
 }
 
 #[cfg(target_os = "macos")]
@@ -119,3 +299,63 @@ pub fn hostly_open_url(url: String) -> Result<(), String> {
     }
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn generated_hosts_parse_back() {
+        let profiles = vec![
+            ("Dev".to_string(), "127.0.0.1 dev.local\n".to_string()),
+            ("Test".to_string(), "# no trailing newline\n10.0.0.1 test.local".to_string()),
+        ];
+        let content = generate_hosts("127.0.0.1 localhost", &profiles);
+
+        let sections = parse_generated_hosts(&content).unwrap();
+        assert_eq!(sections.common.as_deref(), Some("127.0.0.1 localhost"));
+        assert_eq!(sections.profiles, profiles);
+        // Windows line endings, e.g. after an editor touched the file
+        let sections = parse_generated_hosts(&content.replace('\n', "\r\n")).unwrap();
+        assert_eq!(sections.profiles[0], ("Dev".to_string(), "127.0.0.1 dev.local\r\n".to_string()));
+    }
+
+    #[test]
+    fn foreign_hosts_are_not_parsed() {
+        assert!(parse_generated_hosts("127.0.0.1 localhost\n::1 localhost\n").is_none());
+        assert!(parse_generated_hosts("").is_none());
+    }
+
+    #[test]
+    fn truncated_sections_keep_what_is_there() {
+        let content = generate_hosts("", &[("Dev".to_string(), "10.0.0.1 a\n10.0.0.2 b\n".to_string())]);
+        let truncated = &content[..content.find("10.0.0.2").unwrap()];
+
+        let sections = parse_generated_hosts(truncated).unwrap();
+        assert_eq!(sections.common.as_deref(), Some(""));
+        assert_eq!(sections.profiles, [("Dev".to_string(), "10.0.0.1 a\n".to_string())]);
+
+        let sections = parse_generated_hosts(&format!("{}\n{}\n", GENERATED_HEADER, profile_marker("Empty"))).unwrap();
+        assert_eq!(sections.common, None);
+        assert_eq!(sections.profiles, [("Empty".to_string(), String::new())]);
+    }
+
+    #[test]
+    fn entries_and_unrecognized_lines_are_counted() {
+        let content = "# comment\n\n127.0.0.1 localhost # inline\n::1 ip6-localhost ip6-loopback\n0.0.0.0\nnot-an-ip host\n  \t\n";
+        assert_eq!(count_entries(content), (2, 2));
+        assert_eq!(count_entries("# only\n# comments\n"), (0, 0));
+        assert_eq!(count_entries(""), (0, 0));
+    }
+
+    #[test]
+    fn entry_diff_counts_hosts() {
+        let old = "1.1.1.1 a.test b.test\n2.2.2.2 c.test\n3.3.3.3 gone.test\n";
+        let new = "# c moved\n1.1.1.1 A.test\n1.1.1.1 b.test\n9.9.9.9 c.test\n4.4.4.4 new.test other.test\n";
+        assert_eq!(diff_entries(old, new), EntryDiff { added: 2, removed: 1, changed: 1 });
+        assert_eq!(diff_entries(old, old), EntryDiff::default());
+        assert_eq!(diff_entries("", old), EntryDiff { added: 4, removed: 0, changed: 0 });
+        // A host gaining a second address counts as changed
+        assert_eq!(diff_entries("1.1.1.1 a.test\n", "1.1.1.1 a.test\n::1 a.test\n"), EntryDiff { added: 0, removed: 0, changed: 1 });
+    }
+}
@@ -0,0 +1,114 @@
+use crate::cli::{self, ForwardedOutput};
+use crate::control;
+use crate::storage::Context;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io::{Read, Write};
+use std::net::{Shutdown, TcpListener, TcpStream};
+use std::path::PathBuf;
+use std::time::Duration;
+use tauri::{AppHandle, Emitter};
+
+/// Port and token of the running GUI, next to config.json. A second launch hands its command
+/// line over through it and prints what comes back.
+const INSTANCE_FILE: &str = "instance.json";
+/// Command lines are short, anything bigger is not from us.
+const MAX_REQUEST_SIZE: u64 = 256 * 1024;
+/// A launch sends its command line right after connecting; a client that doesn't is dropped
+/// instead of keeping its thread forever.
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+
+#[derive(Debug, Serialize, Deserialize)]
+struct InstanceEndpoint {
+    port: u16,
+    token: String,
+    pid: u32,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct ForwardRequest {
+    token: String,
+    args: Vec<String>,
+    /// Working directory of the forwarding process, for relative paths
+    cwd: String,
+}
+
+fn instance_path(ctx: &Context) -> Result<PathBuf, String> {
+    Ok(ctx.get_app_dir()?.join(INSTANCE_FILE))
+}
+
+/// Accepts command lines from later launches on a loopback port, each one runs on its own
+/// thread since commands may download or write the hosts file.
+pub fn listen(app: AppHandle) -> Result<(), String> {
+    let listener = TcpListener::bind(("127.0.0.1", 0)).map_err(|e| format!("Failed to accept forwarded commands: {}", e))?;
+    let port = listener.local_addr().map_err(|e| e.to_string())?.port();
+    let token = format!("{}{}", uuid::Uuid::new_v4().simple(), uuid::Uuid::new_v4().simple());
+    let endpoint = InstanceEndpoint { port, token: token.clone(), pid: std::process::id() };
+    let content = serde_json::to_string_pretty(&endpoint).map_err(|e| e.to_string())?;
+    control::write_private_file(&instance_path(&Context::Tauri(&app))?, &content)?;
+
+    std::thread::spawn(move || {
+        for stream in listener.incoming().flatten() {
+            let app = app.clone();
+            let token = token.clone();
+            std::thread::spawn(move || serve(&app, &token, stream));
+        }
+    });
+    Ok(())
+}
+
+fn serve(app: &AppHandle, token: &str, mut stream: TcpStream) {
+    if stream.set_read_timeout(Some(REQUEST_TIMEOUT)).is_err() {
+        return;
+    }
+    let mut request = String::new();
+    if (&mut stream).take(MAX_REQUEST_SIZE).read_to_string(&mut request).is_err() {
+        return;
+    }
+    let Ok(request) = serde_json::from_str::<ForwardRequest>(&request) else {
+        return;
+    };
+    if !control::constant_time_eq(request.token.as_bytes(), token.as_bytes()) {
+        return;
+    }
+    let output = cli::run_forwarded(app, request.args.clone(), &request.cwd);
+    let _ = app.emit("cli-forwarded", serde_json::json!({ "args": request.args.get(1..).unwrap_or_default() }));
+    if let Ok(response) = serde_json::to_vec(&output) {
+        let _ = stream.write_all(&response);
+    }
+}
+
+/// Runs the command line of this launch in the running GUI, prints its output and returns its
+/// exit code. `None` if no instance is listening, the caller then starts normally.
+pub fn forward(args: &[String]) -> Option<i32> {
+    let ctx = Context::Headless;
+    let endpoint: InstanceEndpoint = serde_json::from_str(&fs::read_to_string(instance_path(&ctx).ok()?).ok()?).ok()?;
+    // Refused when the file was left behind by an instance that is gone
+    let mut stream = TcpStream::connect(("127.0.0.1", endpoint.port)).ok()?;
+
+    let request = ForwardRequest {
+        token: endpoint.token,
+        args: args.to_vec(),
+        cwd: std::env::current_dir().map(|d| d.display().to_string()).unwrap_or_default(),
+    };
+    match exchange(&mut stream, &request) {
+        Ok(output) => {
+            print!("{}", output.stdout);
+            eprint!("{}", output.stderr);
+            Some(output.code)
+        }
+        Err(e) => {
+            eprintln!("Failed to run the command in the running Hostly instance (pid {}): {}", endpoint.pid, e);
+            Some(1)
+        }
+    }
+}
+
+fn exchange(stream: &mut TcpStream, request: &ForwardRequest) -> Result<ForwardedOutput, String> {
+    let request = serde_json::to_vec(request).map_err(|e| e.to_string())?;
+    stream.write_all(&request).map_err(|e| e.to_string())?;
+    stream.shutdown(Shutdown::Write).map_err(|e| e.to_string())?;
+    let mut response = String::new();
+    stream.read_to_string(&mut response).map_err(|e| e.to_string())?;
+    serde_json::from_str(&response).map_err(|_| "no answer".to_string())
+}
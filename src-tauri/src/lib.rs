@@ -1,15 +1,58 @@
 mod hosts;
 pub mod storage;
 pub mod cli;
+pub mod network;
+pub mod switchhosts;
+pub mod archive;
+pub mod git_sync;
+pub mod watcher;
+pub mod remote;
+pub mod proxy;
+pub mod formats;
+pub mod transform;
+pub mod scheduler;
+pub mod logging;
+pub mod daemon;
+pub mod control;
+pub mod events;
+pub mod instance;
 
 #[cfg(target_os = "windows")]
 use window_vibrancy::apply_mica;
-use tauri::Manager;
-
+use tauri::{Emitter, Manager};
 
 #[cfg_attr(mobile, tauri::mobile_entry_point)]
 pub fn run() {
+    // Read-only commands print here instead of being forwarded to a running instance
+    if cli::run_standalone() {
+        return;
+    }
+    // Everything else runs in the GUI if one is open, with its output and exit status relayed
+    let args: Vec<String> = std::env::args().collect();
+    if args.len() > 1 {
+        if let Some(code) = instance::forward(&args) {
+            std::process::exit(code);
+        }
+    }
+
     tauri::Builder::default()
+        // Must come first: a second launch hands its arguments to the running instance and exits
+        .plugin(tauri_plugin_single_instance::init(|app, args, cwd| {
+            if args.len() > 1 {
+                // Only reached when `instance::forward` could not connect (e.g. the instance is
+                // still starting), the output is lost then.
+                // Commands may download or write the hosts file, keep the event loop free
+                let app = app.clone();
+                std::thread::spawn(move || {
+                    cli::run_forwarded(&app, args.clone(), &cwd);
+                    let _ = app.emit("cli-forwarded", serde_json::json!({ "args": &args[1..] }));
+                });
+            } else if let Some(window) = app.get_webview_window("main") {
+                let _ = window.unminimize();
+                let _ = window.show();
+                let _ = window.set_focus();
+            }
+        }))
         .plugin(tauri_plugin_fs::init())
         .plugin(tauri_plugin_dialog::init())
         .setup(|app| {
@@ -18,6 +61,10 @@ pub fn run() {
                 std::process::exit(0);
             }
 
+            if let Err(e) = instance::listen(app.handle().clone()) {
+                eprintln!("{}", e);
+            }
+
             let window = app.get_webview_window("main").unwrap();
 
             #[cfg(target_os = "windows")]
@@ -38,15 +85,24 @@ pub fn run() {
             // window.show().unwrap();
             // window.set_focus().unwrap();
             
-            // Background Scheduler
+            // Remote refresh scheduler
             let handle = app.handle().clone();
             tauri::async_runtime::spawn(async move {
                 // Wait for app to startup
                 tokio::time::sleep(std::time::Duration::from_secs(5)).await;
-                loop {
-                    tokio::time::sleep(std::time::Duration::from_secs(1)).await;
-                    storage::check_auto_updates(&handle);
-                }
+                scheduler::run(scheduler::SchedulerContext::Tauri(handle)).await;
+            });
+
+            // Local control API, opt-in
+            if let Err(e) = control::start(scheduler::SchedulerContext::Tauri(app.handle().clone())) {
+                eprintln!("{}", e);
+            }
+
+            // File and network watchers
+            let handle = app.handle().clone();
+            tauri::async_runtime::spawn(async move {
+                tokio::time::sleep(std::time::Duration::from_secs(5)).await;
+                scheduler::watch(scheduler::SchedulerContext::Tauri(handle)).await;
             });
 
             Ok(())
@@ -64,19 +120,45 @@ pub fn run() {
             storage::save_profile_content,
             storage::delete_profile,
             storage::rename_profile,
+            storage::set_profile_folder,
+            storage::set_storage_layout,
             storage::toggle_profile_active,
             storage::set_multi_select,
             storage::apply_config,
+            storage::adopt_hosts,
             storage::import_file,
             storage::export_file,
             storage::import_data,
             storage::export_data,
-            storage::import_switchhosts,
-            storage::update_remote_config,
-            storage::trigger_profile_update,
+            archive::import_archive,
+            archive::export_archive,
+            git_sync::enable_git_sync,
+            git_sync::disable_git_sync,
+            git_sync::git_pull,
+            git_sync::git_push,
+            switchhosts::import_switchhosts,
+            switchhosts::export_switchhosts,
+            remote::update_remote_config,
+            remote::trigger_profile_update,
+            remote::set_max_retry_backoff,
+            remote::set_content_checks,
+            remote::set_remote_options,
+            remote::preview_remote,
+            remote::get_request_settings,
+            remote::get_update_log,
+            remote::set_request_settings,
+            proxy::set_proxy,
             storage::set_theme,
             storage::save_window_config,
             storage::save_sidebar_config,
+            storage::save_scene,
+            storage::delete_scene,
+            storage::activate_scene,
+            network::detect_network,
+            network::save_network_rule,
+            network::delete_network_rule,
+            control::get_control_api,
+            control::set_control_api,
             show_main_window,
         ])
         .run(tauri::generate_context!())
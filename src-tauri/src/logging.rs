@@ -0,0 +1,106 @@
+use clap::ValueEnum;
+use std::sync::atomic::{AtomicBool, AtomicU8, Ordering};
+
+/// How background tasks (scheduler, watchers) write their log lines.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum LogFormat {
+    /// Message only, what the GUI and CLI have always printed
+    Plain,
+    /// `<timestamp> <LEVEL> <message> key=value ...`
+    Text,
+    /// One JSON object per line
+    Json,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Level {
+    Info,
+    Warn,
+    Error,
+}
+
+impl Level {
+    fn as_str(self) -> &'static str {
+        match self {
+            Level::Info => "info",
+            Level::Warn => "warn",
+            Level::Error => "error",
+        }
+    }
+}
+
+static FORMAT: AtomicU8 = AtomicU8::new(LogFormat::Plain as u8);
+
+static QUIET: AtomicBool = AtomicBool::new(false);
+
+pub fn set_format(format: LogFormat) {
+    FORMAT.store(format as u8, Ordering::Relaxed);
+}
+
+/// Drops info lines, for `--quiet`.
+pub fn set_quiet(quiet: bool) {
+    QUIET.store(quiet, Ordering::Relaxed);
+}
+
+fn format() -> LogFormat {
+    match FORMAT.load(Ordering::Relaxed) {
+        x if x == LogFormat::Text as u8 => LogFormat::Text,
+        x if x == LogFormat::Json as u8 => LogFormat::Json,
+        _ => LogFormat::Plain,
+    }
+}
+
+/// Writes one log line to stderr, whatever the level: stdout belongs to command output, which
+/// may be a JSON document.
+pub fn log(level: Level, msg: &str, fields: &[(&str, &str)]) {
+    if level == Level::Info && QUIET.load(Ordering::Relaxed) {
+        return;
+    }
+    let line = match format() {
+        LogFormat::Plain => msg.to_string(),
+        LogFormat::Text => {
+            let mut line = format!(
+                "{} {} {}",
+                chrono::Local::now().to_rfc3339_opts(chrono::SecondsFormat::Secs, false),
+                level.as_str().to_uppercase(),
+                msg
+            );
+            for (key, value) in fields {
+                line.push_str(&format!(" {}={}", key, quote(value)));
+            }
+            line
+        }
+        LogFormat::Json => {
+            let mut object = serde_json::Map::new();
+            object.insert("ts".into(), chrono::Local::now().to_rfc3339().into());
+            object.insert("level".into(), level.as_str().into());
+            object.insert("msg".into(), msg.into());
+            for (key, value) in fields {
+                object.insert((*key).into(), (*value).into());
+            }
+            serde_json::Value::Object(object).to_string()
+        }
+    };
+    eprintln!("{}", line);
+}
+
+pub fn info(msg: &str, fields: &[(&str, &str)]) {
+    log(Level::Info, msg, fields);
+}
+
+pub fn warn(msg: &str, fields: &[(&str, &str)]) {
+    log(Level::Warn, msg, fields);
+}
+
+pub fn error(msg: &str, fields: &[(&str, &str)]) {
+    log(Level::Error, msg, fields);
+}
+
+/// Quotes values containing whitespace or quotes, logfmt style.
+fn quote(value: &str) -> String {
+    if value.is_empty() || value.chars().any(|c| c.is_whitespace() || c == '"' || c == '=') {
+        format!("{:?}", value)
+    } else {
+        value.to_string()
+    }
+}
@@ -45,7 +45,10 @@ fn check_admin_and_relaunch() {
 }
 
 fn main() {
-    println!("Starting Hostly...");
+    // Command lines may ask for JSON on stdout
+    if std::env::args().len() <= 1 {
+        println!("Starting Hostly...");
+    }
     check_admin_and_relaunch();
 
     hostly_lib::run()
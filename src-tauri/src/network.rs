@@ -0,0 +1,483 @@
+use crate::logging;
+use crate::storage::{self, Context};
+use serde::{Deserialize, Serialize};
+use std::net::IpAddr;
+use std::sync::Mutex;
+use tauri::AppHandle;
+use uuid::Uuid;
+
+/// The network environment a rule is matched against.
+#[derive(Debug, Serialize, Deserialize, Clone, Default, PartialEq)]
+pub struct NetworkSnapshot {
+    /// Non-loopback addresses of all local interfaces
+    pub addresses: Vec<IpAddr>,
+    /// Default gateways (IPv4 and IPv6)
+    pub gateways: Vec<IpAddr>,
+    /// DNS search domains, lowercased
+    pub dns_search_domains: Vec<String>,
+    /// Names of interfaces that currently have an address
+    pub interfaces: Vec<String>,
+}
+
+/// Source of network snapshots. The scheduler uses `SystemNetworkDetector`;
+/// anything else (e.g. a fixed snapshot) can be plugged in for testing.
+pub trait NetworkDetector {
+    fn detect(&self) -> Result<NetworkSnapshot, String>;
+}
+
+impl NetworkDetector for NetworkSnapshot {
+    fn detect(&self) -> Result<NetworkSnapshot, String> {
+        Ok(self.clone())
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum RuleTarget {
+    Profile { id: String },
+    Scene { id: String },
+}
+
+/// Every non-empty list must have at least one match for the rule to apply.
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct NetworkConditions {
+    /// CIDR ranges ("10.8.0.0/16", "fd00::/8"), single addresses or textual prefixes ("192.168.1.")
+    #[serde(default)]
+    pub ip_prefixes: Vec<String>,
+    /// Default gateway, same syntax as `ip_prefixes`
+    #[serde(default)]
+    pub gateways: Vec<String>,
+    /// DNS search domains, matched on the domain itself or any parent ("corp.example.com")
+    #[serde(default)]
+    pub dns_domains: Vec<String>,
+    /// Interface names, a trailing `*` matches any suffix ("utun*")
+    #[serde(default)]
+    pub interfaces: Vec<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct NetworkRule {
+    #[serde(default)]
+    pub id: String,
+    pub name: String,
+    #[serde(default = "default_enabled")]
+    pub enabled: bool,
+    #[serde(default)]
+    pub conditions: NetworkConditions,
+    pub target: RuleTarget,
+}
+
+fn default_enabled() -> bool {
+    true
+}
+
+impl NetworkRule {
+    pub fn matches(&self, snapshot: &NetworkSnapshot) -> bool {
+        let c = &self.conditions;
+        if c.ip_prefixes.is_empty() && c.gateways.is_empty() && c.dns_domains.is_empty() && c.interfaces.is_empty() {
+            // A rule without conditions would match everywhere and fight every other rule
+            return false;
+        }
+
+        let any_addr = |patterns: &Vec<String>, addrs: &Vec<IpAddr>| {
+            patterns.is_empty() || patterns.iter().any(|p| addrs.iter().any(|a| ip_matches(p, a)))
+        };
+        let any_domain = c.dns_domains.is_empty() || c.dns_domains.iter().any(|d| {
+            let d = d.trim().trim_start_matches('.').to_lowercase();
+            snapshot.dns_search_domains.iter().any(|s| *s == d || s.ends_with(&format!(".{}", d)))
+        });
+        let any_iface = c.interfaces.is_empty() || c.interfaces.iter().any(|pattern| {
+            let pattern = pattern.trim();
+            snapshot.interfaces.iter().any(|name| match pattern.strip_suffix('*') {
+                Some(prefix) => name.starts_with(prefix),
+                None => name == pattern,
+            })
+        });
+
+        any_addr(&c.ip_prefixes, &snapshot.addresses) && any_addr(&c.gateways, &snapshot.gateways) && any_domain && any_iface
+    }
+}
+
+/// Returns the first enabled rule matching the snapshot; rule order is priority order.
+pub fn match_rule<'a>(rules: &'a [NetworkRule], snapshot: &NetworkSnapshot) -> Option<&'a NetworkRule> {
+    rules.iter().find(|r| r.enabled && r.matches(snapshot))
+}
+
+/// Matches an address against a CIDR block, an exact address or a textual prefix like `10.1.`.
+pub fn ip_matches(pattern: &str, addr: &IpAddr) -> bool {
+    let pattern = pattern.trim();
+    if let Some((net, len)) = pattern.split_once('/') {
+        let (Ok(net), Ok(len)) = (net.parse::<IpAddr>(), len.parse::<u32>()) else {
+            return false;
+        };
+        return match (net, addr) {
+            (IpAddr::V4(net), IpAddr::V4(a)) if len <= 32 => {
+                let mask = u32::MAX.checked_shl(32 - len).unwrap_or(0);
+                u32::from(net) & mask == u32::from(*a) & mask
+            }
+            (IpAddr::V6(net), IpAddr::V6(a)) if len <= 128 => {
+                let mask = u128::MAX.checked_shl(128 - len).unwrap_or(0);
+                u128::from(net) & mask == u128::from(*a) & mask
+            }
+            _ => false,
+        };
+    }
+    if let Ok(ip) = pattern.parse::<IpAddr>() {
+        return ip == *addr;
+    }
+    !pattern.is_empty() && addr.to_string().starts_with(pattern)
+}
+
+/// Reads the current network environment from the operating system.
+pub struct SystemNetworkDetector;
+
+impl NetworkDetector for SystemNetworkDetector {
+    fn detect(&self) -> Result<NetworkSnapshot, String> {
+        let mut snapshot = NetworkSnapshot::default();
+
+        let ifaces = if_addrs::get_if_addrs().map_err(|e| format!("Failed to read network interfaces: {}", e))?;
+        for iface in ifaces.iter().filter(|i| !i.is_loopback()) {
+            snapshot.addresses.push(iface.ip());
+            if !snapshot.interfaces.contains(&iface.name) {
+                snapshot.interfaces.push(iface.name.clone());
+            }
+        }
+
+        // Gateway and DNS lookups are best effort: a missing piece simply never matches
+        snapshot.gateways = read_default_gateways();
+        snapshot.dns_search_domains = read_dns_search_domains();
+        Ok(snapshot)
+    }
+}
+
+#[cfg(target_os = "linux")]
+fn read_default_gateways() -> Vec<IpAddr> {
+    let mut gateways = Vec::new();
+
+    // Destination and gateway are little-endian hex: "00000000  0101A8C0" = default via 192.168.1.1
+    if let Ok(table) = std::fs::read_to_string("/proc/net/route") {
+        for line in table.lines().skip(1) {
+            let cols: Vec<&str> = line.split_whitespace().collect();
+            if cols.len() > 2 && cols[1] == "00000000" {
+                if let Ok(gw) = u32::from_str_radix(cols[2], 16) {
+                    if gw != 0 {
+                        gateways.push(IpAddr::V4(std::net::Ipv4Addr::from(gw.to_le_bytes())));
+                    }
+                }
+            }
+        }
+    }
+
+    // dest, dest_len, src, src_len, next_hop, ... ; default route has an all-zero /0 destination
+    if let Ok(table) = std::fs::read_to_string("/proc/net/ipv6_route") {
+        for line in table.lines() {
+            let cols: Vec<&str> = line.split_whitespace().collect();
+            if cols.len() > 4 && cols[1] == "00" && cols[0].chars().all(|c| c == '0') {
+                if let Ok(gw) = u128::from_str_radix(cols[4], 16) {
+                    if gw != 0 {
+                        gateways.push(IpAddr::V6(std::net::Ipv6Addr::from(gw)));
+                    }
+                }
+            }
+        }
+    }
+
+    gateways
+}
+
+#[cfg(target_os = "macos")]
+fn read_default_gateways() -> Vec<IpAddr> {
+    let output = match std::process::Command::new("netstat").arg("-rn").output() {
+        Ok(o) => o,
+        Err(_) => return Vec::new(),
+    };
+    String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .filter(|l| l.starts_with("default"))
+        .filter_map(|l| l.split_whitespace().nth(1))
+        // IPv6 gateways carry a zone suffix ("fe80::1%en0")
+        .filter_map(|gw| gw.split('%').next()?.parse::<IpAddr>().ok())
+        .collect()
+}
+
+#[cfg(target_os = "windows")]
+fn read_default_gateways() -> Vec<IpAddr> {
+    let output = match std::process::Command::new("route").args(["print", "0.0.0.0"]).output() {
+        Ok(o) => o,
+        Err(_) => return Vec::new(),
+    };
+    String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .filter_map(|l| {
+            let cols: Vec<&str> = l.split_whitespace().collect();
+            if cols.len() > 2 && cols[0] == "0.0.0.0" && cols[1] == "0.0.0.0" {
+                cols[2].parse::<IpAddr>().ok()
+            } else {
+                None
+            }
+        })
+        .collect()
+}
+
+#[cfg(not(target_os = "windows"))]
+fn read_dns_search_domains() -> Vec<String> {
+    let content = std::fs::read_to_string("/etc/resolv.conf").unwrap_or_default();
+    let mut domains: Vec<String> = Vec::new();
+    for line in content.lines() {
+        let mut parts = line.split_whitespace();
+        if let Some("search") | Some("domain") = parts.next() {
+            for d in parts {
+                let d = d.trim_end_matches('.').to_lowercase();
+                if !domains.contains(&d) {
+                    domains.push(d);
+                }
+            }
+        }
+    }
+    domains
+}
+
+#[cfg(target_os = "windows")]
+fn read_dns_search_domains() -> Vec<String> {
+    // `ipconfig /all` output is localized, the DnsClient cmdlets are not
+    let script = "(Get-DnsClientGlobalSetting).SuffixSearchList; Get-DnsClient | ForEach-Object { $_.ConnectionSpecificSuffix }";
+    let output = match std::process::Command::new("powershell").args(["-NoProfile", "-Command", script]).output() {
+        Ok(o) => o,
+        Err(_) => return Vec::new(),
+    };
+    let mut domains: Vec<String> = Vec::new();
+    for d in String::from_utf8_lossy(&output.stdout).lines() {
+        let d = d.trim().trim_end_matches('.').to_lowercase();
+        if !d.is_empty() && !domains.contains(&d) {
+            domains.push(d);
+        }
+    }
+    domains
+}
+
+/// Id of the rule applied by the last check, so a rule only fires when the network changes
+/// and the user is free to switch profiles manually in between. Only updated once the
+/// switch went through, a failed apply is retried on the next check.
+static LAST_MATCHED_RULE: Mutex<Option<String>> = Mutex::new(None);
+
+fn reset_last_match() {
+    *LAST_MATCHED_RULE.lock().unwrap_or_else(|e| e.into_inner()) = None;
+}
+
+/// Evaluates the network rules against the detector's snapshot and activates the target of
+/// the first matching rule. Returns `true` if the hosts file was re-applied.
+pub fn check_network_rules(ctx: &Context, detector: &dyn NetworkDetector) -> Result<bool, String> {
+    switch_for_network(ctx, detector, storage::apply_config_internal)
+}
+
+/// `check_network_rules` with the hosts file write passed in, tests leave the real one alone.
+fn switch_for_network(ctx: &Context, detector: &dyn NetworkDetector, apply: impl Fn(&Context) -> Result<(), String>) -> Result<bool, String> {
+    let config = storage::load_config_internal(ctx)?;
+    if config.network_rules.is_empty() {
+        return Ok(false);
+    }
+
+    let snapshot = detector.detect()?;
+    let matched = match_rule(&config.network_rules, &snapshot);
+    let matched_id = matched.map(|r| r.id.clone());
+    if *LAST_MATCHED_RULE.lock().unwrap_or_else(|e| e.into_inner()) == matched_id {
+        return Ok(false);
+    }
+
+    if let Some(rule) = matched {
+        logging::info(&format!("Network rule '{}' matched, switching profiles...", rule.name), &[("event", "network_rule"), ("rule", &rule.name)]);
+        match &rule.target {
+            RuleTarget::Profile { id } => storage::activate_profile_internal(ctx, id)?,
+            RuleTarget::Scene { id } => storage::activate_scene_internal(ctx, id)?,
+        }
+        apply(ctx)?;
+    }
+    *LAST_MATCHED_RULE.lock().unwrap_or_else(|e| e.into_inner()) = matched_id.clone();
+    Ok(matched_id.is_some())
+}
+
+#[tauri::command]
+pub fn detect_network() -> Result<NetworkSnapshot, String> {
+    SystemNetworkDetector.detect()
+}
+
+#[tauri::command]
+pub fn save_network_rule(app: AppHandle, rule: NetworkRule) -> Result<String, String> {
+    save_network_rule_internal(&Context::Tauri(&app), rule)
+}
+
+pub fn save_network_rule_internal(ctx: &Context, mut rule: NetworkRule) -> Result<String, String> {
+    let mut config = storage::load_config_internal(ctx)?;
+
+    let target_exists = match &rule.target {
+        RuleTarget::Profile { id } => config.profiles.iter().any(|p| &p.id == id),
+        RuleTarget::Scene { id } => config.scenes.iter().any(|s| &s.id == id),
+    };
+    if !target_exists {
+        return Err("Rule target not found".to_string());
+    }
+
+    if rule.id.is_empty() {
+        rule.id = Uuid::new_v4().to_string();
+        config.network_rules.push(rule.clone());
+    } else if let Some(existing) = config.network_rules.iter_mut().find(|r| r.id == rule.id) {
+        *existing = rule.clone();
+    } else {
+        return Err("Rule not found".to_string());
+    }
+
+    storage::save_config_internal(ctx, &config)?;
+    // Let the edited rule fire on the next check even if the network did not change
+    reset_last_match();
+    Ok(rule.id)
+}
+
+#[tauri::command]
+pub fn delete_network_rule(app: AppHandle, id: String) -> Result<(), String> {
+    delete_network_rule_internal(&Context::Tauri(&app), &id)
+}
+
+pub fn delete_network_rule_internal(ctx: &Context, id: &str) -> Result<(), String> {
+    let mut config = storage::load_config_internal(ctx)?;
+    config.network_rules.retain(|r| r.id != id);
+    storage::save_config_internal(ctx, &config)?;
+    reset_last_match();
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ip(s: &str) -> IpAddr {
+        s.parse().unwrap()
+    }
+
+    fn rule(id: &str, conditions: NetworkConditions) -> NetworkRule {
+        NetworkRule {
+            id: id.to_string(),
+            name: id.to_string(),
+            enabled: true,
+            conditions,
+            target: RuleTarget::Profile { id: "p".to_string() },
+        }
+    }
+
+    fn office() -> NetworkSnapshot {
+        NetworkSnapshot {
+            addresses: vec![ip("10.8.3.17"), ip("fd00::17")],
+            gateways: vec![ip("10.8.0.1")],
+            dns_search_domains: vec!["eng.corp.example.com".to_string()],
+            interfaces: vec!["en0".to_string(), "utun3".to_string()],
+        }
+    }
+
+    #[test]
+    fn ip_patterns() {
+        assert!(ip_matches("10.8.0.0/16", &ip("10.8.3.17")));
+        assert!(!ip_matches("10.9.0.0/16", &ip("10.8.3.17")));
+        assert!(ip_matches("0.0.0.0/0", &ip("1.2.3.4")));
+        assert!(ip_matches("fd00::/8", &ip("fd00::17")));
+        assert!(!ip_matches("fd00::/8", &ip("10.8.3.17")));
+        assert!(ip_matches("10.8.3.17", &ip("10.8.3.17")));
+        assert!(ip_matches("10.8.", &ip("10.8.3.17")));
+        assert!(!ip_matches("10.80.", &ip("10.8.3.17")));
+        assert!(!ip_matches("10.8.0.0/33", &ip("10.8.3.17")));
+        assert!(!ip_matches("", &ip("10.8.3.17")));
+    }
+
+    #[test]
+    fn every_condition_list_must_match() {
+        let both = rule("r", NetworkConditions {
+            ip_prefixes: vec!["10.8.0.0/16".to_string()],
+            dns_domains: vec!["corp.example.com".to_string()],
+            ..Default::default()
+        });
+        assert!(both.matches(&office()));
+
+        let wrong_gateway = rule("r", NetworkConditions {
+            ip_prefixes: vec!["10.8.0.0/16".to_string()],
+            gateways: vec!["192.168.1.1".to_string()],
+            ..Default::default()
+        });
+        assert!(!wrong_gateway.matches(&office()));
+    }
+
+    #[test]
+    fn domains_and_interfaces() {
+        let parent = rule("r", NetworkConditions { dns_domains: vec![".Example.com".to_string()], ..Default::default() });
+        assert!(parent.matches(&office()));
+        let lookalike = rule("r", NetworkConditions { dns_domains: vec!["ample.com".to_string()], ..Default::default() });
+        assert!(!lookalike.matches(&office()));
+
+        let wildcard = rule("r", NetworkConditions { interfaces: vec!["utun*".to_string()], ..Default::default() });
+        assert!(wildcard.matches(&office()));
+        let exact = rule("r", NetworkConditions { interfaces: vec!["utun".to_string()], ..Default::default() });
+        assert!(!exact.matches(&office()));
+    }
+
+    #[test]
+    fn empty_conditions_never_match() {
+        assert!(!rule("r", NetworkConditions::default()).matches(&office()));
+    }
+
+    #[test]
+    fn first_enabled_match_wins() {
+        let vpn = NetworkConditions { interfaces: vec!["utun*".to_string()], ..Default::default() };
+        let mut disabled = rule("disabled", vpn.clone());
+        disabled.enabled = false;
+        let rules = vec![
+            disabled,
+            rule("home", NetworkConditions { gateways: vec!["192.168.1.1".to_string()], ..Default::default() }),
+            rule("vpn", vpn.clone()),
+            rule("also-vpn", vpn),
+        ];
+        assert_eq!(match_rule(&rules, &office()).map(|r| r.id.as_str()), Some("vpn"));
+        assert!(match_rule(&rules, &NetworkSnapshot::default()).is_none());
+    }
+
+    // One test, LAST_MATCHED_RULE is shared by the whole process
+    #[test]
+    fn rules_fire_once_per_network_change() {
+        let dir = tempfile::tempdir().unwrap();
+        let ctx = Context::Dir(dir.path().to_path_buf());
+        storage::save_config_internal(&ctx, &storage::AppConfig::default()).unwrap();
+        let office_id = storage::create_profile_internal(&ctx, "Office".into(), None, None, None).unwrap();
+        let home_id = storage::create_profile_internal(&ctx, "Home".into(), None, None, None).unwrap();
+        let conditions = NetworkConditions { gateways: vec!["10.8.0.1".to_string()], ..Default::default() };
+        let mut office_rule = rule("", conditions);
+        office_rule.target = RuleTarget::Profile { id: office_id.clone() };
+        save_network_rule_internal(&ctx, office_rule).unwrap();
+
+        let applies = std::cell::Cell::new(0);
+        let apply = |_: &Context| {
+            applies.set(applies.get() + 1);
+            Ok(())
+        };
+        let active = || storage::load_config_internal(&ctx).unwrap().profiles.iter().filter(|p| p.active).map(|p| p.id.clone()).collect::<Vec<_>>();
+        let home = NetworkSnapshot { gateways: vec![ip("192.168.1.1")], ..Default::default() };
+
+        assert!(switch_for_network(&ctx, &office(), apply).unwrap());
+        assert_eq!(active(), [office_id.as_str()]);
+        assert_eq!(applies.get(), 1);
+
+        // Still in the office: a manual switch is left alone
+        storage::activate_profile_internal(&ctx, &home_id).unwrap();
+        assert!(!switch_for_network(&ctx, &office(), apply).unwrap());
+        assert_eq!(active(), [home_id.as_str()]);
+        assert_eq!(applies.get(), 1);
+
+        // Leaving resets the match, so coming back switches again
+        assert!(!switch_for_network(&ctx, &home, apply).unwrap());
+        assert_eq!(active(), [home_id.as_str()]);
+        assert!(switch_for_network(&ctx, &office(), apply).unwrap());
+        assert_eq!(active(), [office_id.as_str()]);
+        assert_eq!(applies.get(), 2);
+
+        // A failed apply is retried on the next check
+        assert!(switch_for_network(&ctx, &home, apply).is_ok());
+        assert!(switch_for_network(&ctx, &office(), |_: &Context| Err("denied".to_string())).is_err());
+        assert!(switch_for_network(&ctx, &office(), apply).unwrap());
+        assert_eq!(applies.get(), 3);
+    }
+}
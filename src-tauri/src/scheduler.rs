@@ -0,0 +1,291 @@
+use crate::events::{self, AppEvent};
+use crate::hosts;
+use crate::logging;
+use crate::network;
+use crate::remote;
+use crate::storage::{self, Context};
+use crate::watcher;
+use std::collections::HashSet;
+use std::sync::{Arc, LazyLock};
+use std::time::Duration;
+use tauri::AppHandle;
+use tokio::sync::{mpsc, Notify, Semaphore};
+
+/// Downloads running at the same time; each one is a blocking thread.
+const MAX_CONCURRENT_REFRESHES: usize = 4;
+/// Longest sleep when nothing is due, so clock jumps (suspend, DST) are noticed eventually.
+const IDLE_WAIT: Duration = Duration::from_secs(60);
+/// Quiet period after a refresh finishes before the hosts file is written, so refreshes
+/// finishing close together share one apply.
+const APPLY_DEBOUNCE: Duration = Duration::from_millis(500);
+/// Longest an apply waits for refreshes that are still running.
+const APPLY_COALESCE_WINDOW: Duration = Duration::from_secs(5);
+/// Network detection spawns processes on some platforms, so it runs every N watcher ticks.
+const NETWORK_CHECK_INTERVAL_SECS: u64 = 10;
+
+static WAKE: LazyLock<Notify> = LazyLock::new(Notify::new);
+
+/// Makes the scheduler recompute due times right away, e.g. after the config changed.
+pub fn wake() {
+    WAKE.notify_one();
+}
+
+/// Owned counterpart of `storage::Context`, so it can move into worker threads.
+#[derive(Clone)]
+pub enum SchedulerContext {
+    Tauri(AppHandle),
+    Headless,
+}
+
+impl SchedulerContext {
+    pub(crate) fn with<R>(&self, f: impl FnOnce(&Context) -> R) -> R {
+        match self {
+            SchedulerContext::Tauri(app) => f(&Context::Tauri(app)),
+            SchedulerContext::Headless => f(&Context::Headless),
+        }
+    }
+}
+
+/// Runs the remote refresh scheduler forever. Sleeps until the next profile is due (or until
+/// `wake` is called), refreshes due profiles on a bounded pool of blocking threads and
+/// re-applies the hosts file once per batch of finished refreshes.
+pub async fn run(ctx: SchedulerContext) {
+    let workers = Arc::new(Semaphore::new(MAX_CONCURRENT_REFRESHES));
+    let (done_tx, mut done_rx) = mpsc::unbounded_channel::<(String, Result<bool, String>)>();
+    let mut in_flight: HashSet<String> = HashSet::new();
+
+    loop {
+        let mut next_due: Option<Duration> = None;
+        match ctx.with(storage::load_config_internal) {
+            Ok(config) => {
+                for p in &config.profiles {
+                    if in_flight.contains(&p.id) {
+                        continue;
+                    }
+                    let Some(due) = remote::next_attempt(&config, p) else {
+                        continue;
+                    };
+                    // Taken after `next_attempt`, which reports never-updated profiles as due "now"
+                    let now = chrono::Local::now();
+                    match (due - now.fixed_offset()).to_std() {
+                        // Not due yet: negative durations fail to convert
+                        Ok(wait) if !wait.is_zero() => {
+                            next_due = Some(next_due.map_or(wait, |d| d.min(wait)));
+                        }
+                        _ => {
+                            in_flight.insert(p.id.clone());
+                            spawn_refresh(&ctx, &workers, &done_tx, p.id.clone());
+                        }
+                    }
+                }
+            }
+            Err(e) => logging::error(&format!("Scheduler failed to load config: {}", e), &[("event", "config_error"), ("error", &e)]),
+        }
+
+        let sleep = next_due.unwrap_or(IDLE_WAIT).min(IDLE_WAIT);
+        tokio::select! {
+            _ = tokio::time::sleep(sleep) => {}
+            _ = WAKE.notified() => {}
+            Some(first) = done_rx.recv() => {
+                let mut changed = Vec::new();
+                let mut finish = |(id, result): (String, Result<bool, String>), in_flight: &mut HashSet<String>| {
+                    in_flight.remove(&id);
+                    match result {
+                        Ok(true) => changed.push(id),
+                        Ok(false) => {}
+                        Err(e) => logging::error(
+                            &format!("Failed to auto-update {}: {}", id, e),
+                            &[("event", "refresh_failed"), ("profile_id", &id), ("error", &e)],
+                        ),
+                    }
+                };
+                finish(first, &mut in_flight);
+
+                // Give the rest of the batch a chance to finish before touching the hosts file
+                let deadline = tokio::time::Instant::now() + APPLY_COALESCE_WINDOW;
+                loop {
+                    let wait = if in_flight.is_empty() {
+                        APPLY_DEBOUNCE
+                    } else {
+                        deadline.saturating_duration_since(tokio::time::Instant::now())
+                    };
+                    match tokio::time::timeout(wait, done_rx.recv()).await {
+                        Ok(Some(next)) => finish(next, &mut in_flight),
+                        _ => break,
+                    }
+                }
+
+                if !changed.is_empty() {
+                    apply_if_active(&ctx, changed).await;
+                }
+            }
+        }
+    }
+}
+
+fn spawn_refresh(
+    ctx: &SchedulerContext,
+    workers: &Arc<Semaphore>,
+    done: &mpsc::UnboundedSender<(String, Result<bool, String>)>,
+    id: String,
+) {
+    let (ctx, workers, done) = (ctx.clone(), workers.clone(), done.clone());
+    tokio::spawn(async move {
+        let Ok(_permit) = workers.acquire_owned().await else {
+            return;
+        };
+        logging::info(&format!("Auto-updating profile {}...", id), &[("event", "refresh_started"), ("profile_id", &id)]);
+        let worker_ctx = ctx.clone();
+        let worker_id = id.clone();
+        let result = tokio::task::spawn_blocking(move || {
+            worker_ctx.with(|c| remote::trigger_profile_update_internal(c, &worker_id))
+        })
+        .await
+        .unwrap_or_else(|e| Err(format!("Refresh worker failed: {}", e)));
+        let _ = done.send((id, result));
+    });
+}
+
+/// Writes the hosts file once if any of the refreshed profiles is switched on.
+async fn apply_if_active(ctx: &SchedulerContext, changed: Vec<String>) {
+    let ctx = ctx.clone();
+    let applied = tokio::task::spawn_blocking(move || {
+        ctx.with(|c| {
+            let config = storage::load_config_internal(c)?;
+            if config.profiles.iter().any(|p| p.active && changed.contains(&p.id)) {
+                storage::apply_config_internal(c)?;
+            }
+            Ok::<(), String>(())
+        })
+    })
+    .await;
+    let error = match applied {
+        Ok(Ok(())) => return,
+        Ok(Err(e)) => e,
+        Err(e) => e.to_string(),
+    };
+    logging::error(&format!("Failed to apply refreshed profiles: {}", error), &[("event", "apply_failed"), ("error", &error)]);
+}
+
+/// Polls profile files, the config (for changes made by other processes), local remote sources,
+/// the hosts file and network rules forever. Cheap enough to poll once a second, unlike remote
+/// downloads. Each tick runs on a blocking thread: a local source may trigger an apply (and
+/// its admin prompt) and network detection spawns processes.
+pub async fn watch(ctx: SchedulerContext) {
+    let mut ticks: u64 = 0;
+    loop {
+        tokio::time::sleep(Duration::from_secs(1)).await;
+        let check_network = ticks.is_multiple_of(NETWORK_CHECK_INTERVAL_SECS);
+        let tick_ctx = ctx.clone();
+        let tick = tokio::task::spawn_blocking(move || tick_ctx.with(|c| {
+            if let Err(e) = watcher::check_profile_files(c) {
+                logging::error(&format!("Failed to check profile files: {}", e), &[("event", "watch_failed"), ("error", &e)]);
+            }
+            if let Err(e) = events::check_external_changes(c) {
+                logging::error(&format!("Failed to check for outside changes: {}", e), &[("event", "watch_failed"), ("error", &e)]);
+            }
+            if let Err(e) = remote::check_local_sources(c) {
+                logging::error(&format!("Failed to check local sources: {}", e), &[("event", "watch_failed"), ("error", &e)]);
+            }
+            match hosts::check_drift() {
+                Ok(Some(path)) => {
+                    logging::warn(&format!("{} was changed outside Hostly", path), &[("event", "hosts_drifted"), ("path", &path)]);
+                    events::emit(c, AppEvent::HostsFileDrifted { path });
+                }
+                Ok(None) => {}
+                Err(e) => logging::error(&format!("Failed to check the hosts file: {}", e), &[("event", "watch_failed"), ("error", &e)]),
+            }
+            if check_network {
+                if let Err(e) = network::check_network_rules(c, &network::SystemNetworkDetector) {
+                    logging::error(&format!("Failed to evaluate network rules: {}", e), &[("event", "network_failed"), ("error", &e)]);
+                }
+            }
+        }))
+        .await;
+        if let Err(e) = tick {
+            let error = e.to_string();
+            logging::error(&format!("Watcher tick failed: {}", error), &[("event", "watch_failed"), ("error", &error)]);
+        }
+        ticks += 1;
+    }
+}
+
+/// Refreshes the given profiles right away, `MAX_CONCURRENT_REFRESHES` at a time, and applies
+/// the hosts file once if an active profile changed. Used by one-shot `update` runs (cron).
+pub fn refresh_now(ctx: &Context, ids: &[String]) -> Vec<(String, Result<bool, String>)> {
+    let mut results = Vec::new();
+    for batch in ids.chunks(MAX_CONCURRENT_REFRESHES) {
+        std::thread::scope(|scope| {
+            let handles: Vec<_> = batch
+                .iter()
+                .map(|id| (id, scope.spawn(|| remote::trigger_profile_update_internal(ctx, id))))
+                .collect();
+            for (id, handle) in handles {
+                let result = handle.join().unwrap_or_else(|_| Err("Refresh worker panicked".to_string()));
+                results.push((id.clone(), result));
+            }
+        });
+    }
+
+    let changed: Vec<&String> = results.iter().filter(|(_, r)| matches!(r, Ok(true))).map(|(id, _)| id).collect();
+    if !changed.is_empty() {
+        let applied = storage::load_config_internal(ctx).and_then(|config| {
+            if config.profiles.iter().any(|p| p.active && changed.contains(&&p.id)) {
+                storage::apply_config_internal(ctx)?;
+            }
+            Ok(())
+        });
+        if let Err(e) = applied {
+            logging::error(&format!("Failed to apply refreshed profiles: {}", e), &[("event", "apply_failed"), ("error", &e)]);
+        }
+    }
+    results
+}
+
+/// Remote profiles whose next refresh is due now.
+pub fn due_profiles(config: &storage::AppConfig) -> Vec<String> {
+    config
+        .profiles
+        .iter()
+        // `now` must be taken after `next_attempt`, which reports never-updated profiles as due "now"
+        .filter(|p| remote::next_attempt(config, p).is_some_and(|due| due <= chrono::Local::now()))
+        .map(|p| p.id.clone())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::{AppConfig, ProfileMetadata};
+
+    fn remote(id: &str, last_update: Option<String>, failure_count: u32) -> ProfileMetadata {
+        ProfileMetadata {
+            id: id.to_string(),
+            url: Some("https://example.com/hosts".to_string()),
+            update_interval: Some(3600),
+            last_attempt: last_update.clone(),
+            last_update,
+            failure_count,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn due_profiles_follow_interval_and_backoff() {
+        let ago = |secs: i64| Some((chrono::Local::now() - chrono::Duration::seconds(secs)).to_rfc3339());
+        let config = AppConfig {
+            profiles: vec![
+                remote("never", None, 0),
+                remote("fresh", ago(60), 0),
+                remote("stale", ago(7200), 0),
+                // Failed a minute ago, the first retry comes after 30 seconds
+                remote("retry", ago(60), 1),
+                // Third failure a minute ago waits two minutes
+                remote("backing-off", ago(60), 3),
+                ProfileMetadata { id: "local".to_string(), ..Default::default() },
+            ],
+            ..Default::default()
+        };
+        assert_eq!(due_profiles(&config), ["never", "stale", "retry"]);
+    }
+}
@@ -1,10 +1,11 @@
+use crate::events::{self, AppEvent};
 use serde::{Deserialize, Serialize};
 use std::fs;
 use std::path::PathBuf;
 use tauri::{AppHandle, Manager};
 use uuid::Uuid;
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
 pub struct ProfileMetadata {
     pub id: String,
     pub name: String,
@@ -15,6 +16,24 @@ pub struct ProfileMetadata {
     pub last_update: Option<String>,
     /// Auto-update interval in seconds (0 or None means manual)
     pub update_interval: Option<u64>,
+    /// Folder path the profile is grouped under, segments separated by '/'
+    #[serde(default)]
+    pub folder: Option<String>,
+    /// Last refresh attempt, successful or not (ISO 8601)
+    #[serde(default)]
+    pub last_attempt: Option<String>,
+    /// Error of the last refresh, cleared once a refresh succeeds
+    #[serde(default)]
+    pub last_error: Option<String>,
+    /// Consecutive failed refreshes, drives the retry backoff
+    #[serde(default)]
+    pub failure_count: u32,
+    /// How the remote sources are fetched and converted
+    #[serde(default)]
+    pub remote_options: crate::remote::RemoteOptions,
+    /// Result of the last fetch of each source URL
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub source_status: Vec<crate::remote::SourceStatus>,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone, Default)]
@@ -31,6 +50,48 @@ pub struct AppConfig {
                                          // Actually, sticking to what I planned: ProfileMetadata has 'active'. 
                                          // But for multi-select logic, we need to know who is active quickly. 
                                          // Let's trust ProfileMetadata.active as source of truth.
+    /// Named sets of profiles that can be switched on together
+    #[serde(default)]
+    pub scenes: Vec<Scene>,
+    /// Rules evaluated by the scheduler to switch profiles/scenes based on the current network
+    #[serde(default)]
+    pub network_rules: Vec<crate::network::NetworkRule>,
+    /// Mirror profiles into a local git working tree (None = disabled)
+    #[serde(default)]
+    pub git_sync: Option<crate::git_sync::GitSyncConfig>,
+    /// How profile files are named on disk
+    #[serde(default)]
+    pub storage_layout: StorageLayout,
+    /// Upper bound in seconds for the delay between retries of a failing remote profile
+    #[serde(default)]
+    pub max_retry_backoff: Option<u64>,
+    /// Sanity checks a download must pass before it replaces a remote profile
+    #[serde(default)]
+    pub content_checks: crate::remote::ContentChecks,
+    /// Proxy for remote downloads
+    #[serde(default)]
+    pub proxy: crate::proxy::ProxySettings,
+    /// Local HTTP endpoint for scripts to switch profiles, off unless enabled
+    #[serde(default)]
+    pub control_api: crate::control::ControlSettings,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Default, clap::ValueEnum)]
+#[serde(rename_all = "snake_case")]
+pub enum StorageLayout {
+    /// `profiles/<id>.txt`, names only in config.json
+    #[default]
+    Uuid,
+    /// `profiles/<folder>/<name>.hosts` plus a `<name>.meta.json` sidecar, editable with any tool
+    Named,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Scene {
+    pub id: String,
+    pub name: String,
+    /// Profiles switched on when the scene is activated; every other profile is switched off
+    pub profile_ids: Vec<String>,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -39,11 +100,17 @@ pub struct ProfileData {
     pub name: String,
     pub content: String,
     pub active: bool,
+    /// Refresh state of remote profiles
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub remote: Option<crate::remote::RemoteStatus>,
 }
 
 pub enum Context<'a> {
     Tauri(&'a AppHandle),
     Headless,
+    /// Store in a scratch directory, keeps tests away from the real one
+    #[cfg(test)]
+    Dir(PathBuf),
 }
 
 impl<'a> Context<'a> {
@@ -72,6 +139,8 @@ impl<'a> Context<'a> {
                     }
                 }
             }
+            #[cfg(test)]
+            Context::Dir(dir) => Ok(dir.clone()),
         }
     }
 }
@@ -115,10 +184,7 @@ pub fn load_config_internal(ctx: &Context) -> Result<AppConfig, String> {
         config.profiles.push(ProfileMetadata {
             id: sys_id,
             name: "系统hosts备份".to_string(),
-            active: false,
-            url: None,
-            last_update: None,
-            update_interval: None,
+            ..Default::default()
         });
 
         // 2. Default Envs
@@ -128,10 +194,7 @@ pub fn load_config_internal(ctx: &Context) -> Result<AppConfig, String> {
              config.profiles.push(ProfileMetadata {
                  id,
                  name: name.to_string(),
-                 active: false,
-                 url: None,
-                 last_update: None,
-                 update_interval: None,
+                 ..Default::default()
              });
         }
         
@@ -151,13 +214,180 @@ pub fn save_config_internal(ctx: &Context, config: &AppConfig) -> Result<(), Str
         }
     }
     let content = serde_json::to_string_pretty(config).map_err(|e| e.to_string())?;
-    fs::write(path, content).map_err(|e| e.to_string())
+    // Write and rename, background refreshes read the config while it is being saved
+    let dir = path.parent().map(|p| p.to_path_buf()).unwrap_or_default();
+    let mut tmp = tempfile::NamedTempFile::new_in(dir).map_err(|e| e.to_string())?;
+    std::io::Write::write_all(&mut tmp, content.as_bytes()).map_err(|e| e.to_string())?;
+    tmp.persist(&path).map_err(|e| e.to_string())?;
+    events::note_written(ctx, config);
+    // Intervals, URLs or retry state may have changed
+    crate::scheduler::wake();
+    Ok(())
+}
+
+/// Reads config.json without creating the first-run defaults.
+fn load_config_if_exists(ctx: &Context) -> Result<Option<AppConfig>, String> {
+    let path = get_config_path(ctx)?;
+    if !path.exists() {
+        return Ok(None);
+    }
+    let content = fs::read_to_string(path).map_err(|e| e.to_string())?;
+    serde_json::from_str(&content).map(Some).map_err(|e| e.to_string())
+}
+
+/// Replaces characters that are not allowed (or not portable) in file names on any platform.
+pub fn profile_file_stem(name: &str) -> String {
+    let stem: String = name.chars().map(|c| if "/\\:*?\"<>|".contains(c) || c.is_control() { '_' } else { c }).collect();
+    // "." and ".." would escape the folder
+    if stem.trim_matches('.').is_empty() { stem.replace('.', "_") } else { stem }
+}
+
+/// Whether two profile names end up in the same file (named layout, git sync): both go through
+/// `profile_file_stem`, and the file systems of Windows and macOS ignore case.
+pub fn same_file_name(a: &str, b: &str) -> bool {
+    profile_file_stem(a).to_lowercase() == profile_file_stem(b).to_lowercase()
+}
+
+/// Whether `name` is used by a profile other than `except`, or would share its file.
+pub fn profile_name_taken(config: &AppConfig, name: &str, except: Option<&str>) -> bool {
+    config.profiles.iter().any(|p| Some(p.id.as_str()) != except && same_file_name(&p.name, name))
+}
+
+/// Pairs of profile names that would share a file under the named layout.
+fn file_name_collisions(config: &AppConfig) -> Vec<String> {
+    let mut collisions = Vec::new();
+    for (i, a) in config.profiles.iter().enumerate() {
+        for b in &config.profiles[i + 1..] {
+            if same_file_name(&a.name, &b.name) {
+                collisions.push(format!("'{}' / '{}'", a.name, b.name));
+            }
+        }
+    }
+    collisions
+}
+
+fn check_profile_name(config: &AppConfig, name: &str, except: Option<&str>) -> Result<(), String> {
+    if config.profiles.iter().any(|p| p.name == name && Some(p.id.as_str()) != except) {
+        return Err("环境名称已存在 / Profile name already exists".to_string());
+    }
+    if let Some(other) = config.profiles.iter().find(|p| Some(p.id.as_str()) != except && same_file_name(&p.name, name)) {
+        return Err(format!("环境文件名冲突 / Profile name '{}' maps to the same file as '{}'", name, other.name));
+    }
+    Ok(())
+}
+
+fn uuid_profile_path(ctx: &Context, id: &str) -> Result<PathBuf, String> {
+    Ok(get_profiles_dir(ctx)?.join(format!("{}.txt", id)))
+}
+
+/// Where the content of profile `id` lives under the layout of `config`. Profiles that are not
+/// (yet) in the config always use the id-based name.
+pub fn profile_file_path(ctx: &Context, config: &AppConfig, id: &str) -> Result<PathBuf, String> {
+    let meta = config.profiles.iter().find(|p| p.id == id);
+    match (config.storage_layout, meta) {
+        (StorageLayout::Named, Some(meta)) => {
+            let mut path = get_profiles_dir(ctx)?;
+            for segment in meta.folder.as_deref().map(normalize_folder).unwrap_or_default().split('/').filter(|s| !s.is_empty()) {
+                path.push(profile_file_stem(segment));
+            }
+            Ok(path.join(format!("{}.hosts", profile_file_stem(&meta.name))))
+        }
+        _ => uuid_profile_path(ctx, id),
+    }
+}
+
+fn sidecar_path(path: &std::path::Path) -> PathBuf {
+    path.with_extension("meta.json")
+}
+
+pub fn write_profile_file(ctx: &Context, config: &AppConfig, id: &str, content: &str) -> Result<(), String> {
+    let path = profile_file_path(ctx, config, id)?;
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent).map_err(|e| e.to_string())?;
+    }
+    fs::write(&path, content).map_err(|e| e.to_string())?;
+
+    if config.storage_layout == StorageLayout::Named {
+        if let Some(meta) = config.profiles.iter().find(|p| p.id == id) {
+            // Only the stable identity/source fields, on/off state changes far too often
+            let mut sidecar = serde_json::to_value(meta).map_err(|e| e.to_string())?;
+            if let Some(obj) = sidecar.as_object_mut() {
+                obj.remove("active");
+                obj.remove("last_update");
+            }
+            let sidecar = serde_json::to_string_pretty(&sidecar).map_err(|e| e.to_string())?;
+            fs::write(sidecar_path(&path), sidecar).map_err(|e| e.to_string())?;
+        }
+    }
+    crate::watcher::note_written(&path);
+    events::note_written(ctx, config);
+    events::emit(ctx, AppEvent::ProfileUpdated { id: id.to_string(), deleted: false });
+    Ok(())
+}
+
+pub fn read_profile_file(ctx: &Context, config: &AppConfig, id: &str) -> Result<String, String> {
+    let path = profile_file_path(ctx, config, id)?;
+    // Content written before the profile was registered still sits at the id-based path
+    let path = if path.exists() { path } else { uuid_profile_path(ctx, id)? };
+    if path.exists() {
+        fs::read_to_string(path).map_err(|e| e.to_string())
+    } else {
+        Ok(String::new())
+    }
+}
+
+fn remove_profile_file(ctx: &Context, config: &AppConfig, id: &str) -> Result<(), String> {
+    let path = profile_file_path(ctx, config, id)?;
+    let _ = fs::remove_file(sidecar_path(&path));
+    let _ = fs::remove_file(&path);
+    let _ = fs::remove_file(uuid_profile_path(ctx, id)?);
+    Ok(())
+}
+
+/// Moves a profile file after its name or folder changed (named layout only).
+fn relocate_profile_file(ctx: &Context, old: &AppConfig, new: &AppConfig, id: &str) -> Result<(), String> {
+    if profile_file_path(ctx, old, id)? == profile_file_path(ctx, new, id)? {
+        return Ok(());
+    }
+    let content = read_profile_file(ctx, old, id)?;
+    remove_profile_file(ctx, old, id)?;
+    write_profile_file(ctx, new, id, &content)
 }
 
 pub fn save_profile_file_internal(ctx: &Context, id: &str, content: &str) -> Result<(), String> {
-    let dir = get_profiles_dir(ctx)?;
-    let path = dir.join(format!("{}.txt", id));
-    fs::write(path, content).map_err(|e| e.to_string())
+    let config = load_config_if_exists(ctx)?.unwrap_or_default();
+    write_profile_file(ctx, &config, id, content)
+}
+
+pub fn read_profile_file_internal(ctx: &Context, id: &str) -> Result<String, String> {
+    let config = load_config_if_exists(ctx)?.unwrap_or_default();
+    read_profile_file(ctx, &config, id)
+}
+
+#[tauri::command]
+pub fn set_storage_layout(app: AppHandle, layout: StorageLayout) -> Result<(), String> {
+    set_storage_layout_internal(&Context::Tauri(&app), layout)
+}
+
+/// Moves every profile file over to `layout`.
+pub fn set_storage_layout_internal(ctx: &Context, layout: StorageLayout) -> Result<(), String> {
+    let old = load_config_internal(ctx)?;
+    if old.storage_layout == layout {
+        return Ok(());
+    }
+
+    let mut new = old.clone();
+    new.storage_layout = layout;
+    let collisions = file_name_collisions(&new);
+    if layout == StorageLayout::Named && !collisions.is_empty() {
+        return Err(format!("Rename these profiles first, they would share a file: {}", collisions.join(", ")));
+    }
+    for p in &old.profiles {
+        let content = read_profile_file(ctx, &old, &p.id)?;
+        remove_profile_file(ctx, &old, &p.id)?;
+        write_profile_file(ctx, &new, &p.id, &content)?;
+    }
+    save_config_internal(ctx, &new)
 }
 
 #[tauri::command]
@@ -181,6 +411,9 @@ pub fn save_common_config(app: AppHandle, content: String) -> Result<(), String>
 
 pub fn save_common_config_internal(ctx: &Context, content: String) -> Result<(), String> {
     let path = get_common_path(ctx)?;
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent).map_err(|e| e.to_string())?;
+    }
     fs::write(path, content).map_err(|e| e.to_string())
 }
 
@@ -226,23 +459,21 @@ pub fn list_profiles(app: AppHandle) -> Result<Vec<ProfileData>, String> {
 
 pub fn list_profiles_internal(ctx: &Context) -> Result<Vec<ProfileData>, String> {
     let config = load_config_internal(ctx)?;
-    let dir = get_profiles_dir(ctx)?;
+    let requests = crate::remote::all_request_settings(ctx);
     
     let mut profiles = Vec::new();
     
-    for meta in config.profiles {
-        let path = dir.join(format!("{}.txt", meta.id));
-        let content = if path.exists() {
-             fs::read_to_string(&path).unwrap_or_default()
-        } else {
-             String::new()
-        };
+    for meta in &config.profiles {
+        let content = read_profile_file(ctx, &config, &meta.id).unwrap_or_default();
+        let remote = crate::remote::status(&config, meta, requests.get(&meta.id));
+        let meta = meta.clone();
         
         profiles.push(ProfileData {
             id: meta.id,
             name: meta.name,
             content,
             active: meta.active,
+            remote,
         });
     }
     
@@ -270,31 +501,30 @@ pub fn create_profile_internal(
     let mut config = load_config_internal(ctx)?;
     
     // Check for duplicate name
-    if config.profiles.iter().any(|p| p.name == name) {
-        return Err("环境名称已存在 / Profile name already exists".to_string());
-    }
+    check_profile_name(&config, &name, None)?;
 
     let id = Uuid::new_v4().to_string();
     let initial_content = content.unwrap_or_default();
-    save_profile_file_internal(ctx, &id, &initial_content)?;
     
     config.profiles.push(ProfileMetadata {
         id: id.clone(),
         name,
-        active: false,
         url,
-        last_update: None,
         update_interval,
+        ..Default::default()
     });
+    write_profile_file(ctx, &config, &id, &initial_content)?;
     
     save_config_internal(ctx, &config)?;
+    crate::git_sync::commit_profile(ctx, &id, None)?;
     Ok(id)
 }
 
 #[tauri::command]
-pub fn save_profile_content(app: AppHandle, id: String, content: String) -> Result<(), String> {
+pub fn save_profile_content(app: AppHandle, id: String, content: String, message: Option<String>) -> Result<(), String> {
     let ctx = Context::Tauri(&app);
     save_profile_content_internal(&ctx, &id, &content)?;
+    crate::git_sync::commit_profile(&ctx, &id, message.as_deref())?;
     
     // If this profile is active, re-apply config to system hosts
     let config = load_config_internal(&ctx)?;
@@ -316,17 +546,20 @@ pub fn delete_profile(app: AppHandle, id: String) -> Result<(), String> {
 pub fn delete_profile_internal(ctx: &Context, id: &str) -> Result<(), String> {
     let mut config = load_config_internal(ctx)?;
     
+    // Delete file (and sidecar) while the config still knows where it lives
+    remove_profile_file(ctx, &config, id)?;
+    
     // Remove from config
     if let Some(idx) = config.profiles.iter().position(|p| p.id == id) {
-        config.profiles.remove(idx);
+        let removed = config.profiles.remove(idx);
+        for scene in &mut config.scenes {
+            scene.profile_ids.retain(|p| p != id);
+        }
+        config.network_rules.retain(|r| r.target != crate::network::RuleTarget::Profile { id: id.to_string() });
         save_config_internal(ctx, &config)?;
-    }
-    
-    // Delete file
-    let dir = get_profiles_dir(ctx)?;
-    let path = dir.join(format!("{}.txt", id));
-    if path.exists() {
-        let _ = fs::remove_file(path);
+        crate::git_sync::move_profile(ctx, &removed, None)?;
+        crate::remote::forget_profile(ctx, id);
+        events::emit(ctx, AppEvent::ProfileUpdated { id: id.to_string(), deleted: true });
     }
     
     Ok(())
@@ -341,17 +574,44 @@ pub fn rename_profile_internal(ctx: &Context, id: &str, new_name: String) -> Res
     let mut config = load_config_internal(ctx)?;
     
     // Check for duplicate name (excluding itself)
-    if config.profiles.iter().any(|p| p.name == new_name && p.id != id) {
-        return Err("环境名称已存在 / Profile name already exists".to_string());
-    }
+    check_profile_name(&config, &new_name, Some(id))?;
 
     if let Some(idx) = config.profiles.iter().position(|p| p.id == id) {
+        let old_config = config.clone();
+        let before = config.profiles[idx].clone();
         config.profiles[idx].name = new_name;
+        relocate_profile_file(ctx, &old_config, &config, id)?;
         save_config_internal(ctx, &config)?;
+        crate::git_sync::move_profile(ctx, &before, Some(&config.profiles[idx]))?;
+        events::emit(ctx, AppEvent::ProfileUpdated { id: id.to_string(), deleted: false });
     }
     Ok(())
 }
 
+#[tauri::command]
+pub fn set_profile_folder(app: AppHandle, id: String, folder: Option<String>) -> Result<(), String> {
+    set_profile_folder_internal(&Context::Tauri(&app), &id, folder)
+}
+
+pub fn set_profile_folder_internal(ctx: &Context, id: &str, folder: Option<String>) -> Result<(), String> {
+    let mut config = load_config_internal(ctx)?;
+    let old_config = config.clone();
+    let p = config.profiles.iter_mut().find(|p| p.id == id).ok_or("Profile not found")?;
+    let before = p.clone();
+    p.folder = folder.map(|f| normalize_folder(&f)).filter(|f| !f.is_empty());
+    let after = p.clone();
+    relocate_profile_file(ctx, &old_config, &config, id)?;
+    save_config_internal(ctx, &config)?;
+    crate::git_sync::move_profile(ctx, &before, Some(&after))?;
+    events::emit(ctx, AppEvent::ProfileUpdated { id: id.to_string(), deleted: false });
+    Ok(())
+}
+
+/// Trims each segment and drops empty ones: " A//B/ " -> "A/B".
+pub fn normalize_folder(folder: &str) -> String {
+    folder.split('/').map(|s| s.trim()).filter(|s| !s.is_empty()).collect::<Vec<_>>().join("/")
+}
+
 #[tauri::command]
 pub fn toggle_profile_active(app: AppHandle, id: String) -> Result<(), String> {
     toggle_profile_active_internal(&Context::Tauri(&app), &id)?;
@@ -385,7 +645,7 @@ pub fn toggle_profile_active_internal(ctx: &Context, id: &str) -> Result<(), Str
         }
     }
     
-    save_config_internal(ctx, &config)
+    save_activation(ctx, &config)
 }
 
 #[tauri::command]
@@ -412,9 +672,107 @@ pub fn set_multi_select_internal(ctx: &Context, enable: bool) -> Result<(), Stri
         }
     }
     
+    save_activation(ctx, &config)
+}
+
+/// Switches a profile on without toggling it off when it is already active.
+pub fn activate_profile_internal(ctx: &Context, id: &str) -> Result<(), String> {
+    let config = load_config_internal(ctx)?;
+    match config.profiles.iter().find(|p| p.id == id) {
+        Some(p) if p.active => Ok(()),
+        Some(_) => toggle_profile_active_internal(ctx, id),
+        None => Err("Profile not found".to_string()),
+    }
+}
+
+/// Switches a profile off, leaving it alone when it is already off.
+pub fn deactivate_profile_internal(ctx: &Context, id: &str) -> Result<(), String> {
+    let config = load_config_internal(ctx)?;
+    match config.profiles.iter().find(|p| p.id == id) {
+        Some(p) if p.active => toggle_profile_active_internal(ctx, id),
+        Some(_) => Ok(()),
+        None => Err("Profile not found".to_string()),
+    }
+}
+
+#[tauri::command]
+pub fn save_scene(app: AppHandle, id: Option<String>, name: String, profile_ids: Vec<String>) -> Result<String, String> {
+    save_scene_internal(&Context::Tauri(&app), id, name, profile_ids)
+}
+
+pub fn save_scene_internal(ctx: &Context, id: Option<String>, name: String, profile_ids: Vec<String>) -> Result<String, String> {
+    let mut config = load_config_internal(ctx)?;
+
+    if config.scenes.iter().any(|s| s.name == name && Some(&s.id) != id.as_ref()) {
+        return Err("场景名称已存在 / Scene name already exists".to_string());
+    }
+
+    let id = match id {
+        Some(id) => {
+            let scene = config.scenes.iter_mut().find(|s| s.id == id).ok_or("Scene not found")?;
+            scene.name = name;
+            scene.profile_ids = profile_ids;
+            id
+        }
+        None => {
+            let id = Uuid::new_v4().to_string();
+            config.scenes.push(Scene { id: id.clone(), name, profile_ids });
+            id
+        }
+    };
+
+    save_config_internal(ctx, &config)?;
+    Ok(id)
+}
+
+#[tauri::command]
+pub fn delete_scene(app: AppHandle, id: String) -> Result<(), String> {
+    delete_scene_internal(&Context::Tauri(&app), &id)
+}
+
+pub fn delete_scene_internal(ctx: &Context, id: &str) -> Result<(), String> {
+    let mut config = load_config_internal(ctx)?;
+    config.scenes.retain(|s| s.id != id);
+    // Rules pointing at the removed scene would never fire again
+    config.network_rules.retain(|r| r.target != crate::network::RuleTarget::Scene { id: id.to_string() });
     save_config_internal(ctx, &config)
 }
 
+#[tauri::command]
+pub fn activate_scene(app: AppHandle, id: String) -> Result<(), String> {
+    activate_scene_internal(&Context::Tauri(&app), &id)?;
+    apply_config(app)
+}
+
+pub fn activate_scene_internal(ctx: &Context, id: &str) -> Result<(), String> {
+    let mut config = load_config_internal(ctx)?;
+    let scene = config.scenes.iter().find(|s| s.id == id).cloned().ok_or("Scene not found")?;
+
+    // A scene with several profiles only makes sense in multi-select mode
+    if scene.profile_ids.len() > 1 {
+        config.multi_select = true;
+    }
+    for p in &mut config.profiles {
+        p.active = scene.profile_ids.contains(&p.id);
+    }
+
+    save_activation(ctx, &config)
+}
+
+/// Saves a config whose active profiles or selection mode changed and tells the frontend.
+fn save_activation(ctx: &Context, config: &AppConfig) -> Result<(), String> {
+    save_config_internal(ctx, config)?;
+    events::emit(ctx, AppEvent::ProfileActivated {
+        active: active_profile_ids(config),
+        multi_select: config.multi_select,
+    });
+    Ok(())
+}
+
+fn active_profile_ids(config: &AppConfig) -> Vec<String> {
+    config.profiles.iter().filter(|p| p.active).map(|p| p.id.clone()).collect()
+}
+
 #[tauri::command]
 pub fn apply_config(app: AppHandle) -> Result<(), String> {
     apply_config_internal(&Context::Tauri(&app))
@@ -424,68 +782,302 @@ pub fn apply_config_internal(ctx: &Context) -> Result<(), String> {
     let config = load_config_internal(ctx)?;
     let common_config = load_common_config_internal(ctx).unwrap_or_default();
     
-    let profiles_dir = get_profiles_dir(ctx)?;
-    let mut merged_content = String::from("# Generated by Hostly\n\n");
-    merged_content.push_str("### Common Config ###\n");
-    merged_content.push_str(&common_config);
-    merged_content.push_str("\n\n");
-
-    let read_profile = |id: &str| -> String {
-        let path = profiles_dir.join(format!("{}.txt", id));
-        if path.exists() {
-             fs::read_to_string(path).unwrap_or_default()
-        } else {
-             String::new()
+    let sections: Vec<(String, String)> = config.profiles.iter()
+        .filter(|profile| profile.active)
+        .map(|profile| (profile.name.clone(), read_profile_file(ctx, &config, &profile.id).unwrap_or_default()))
+        .collect();
+    let merged_content = crate::hosts::generate_hosts(&common_config, &sections);
+
+    match crate::hosts::save_system_hosts(merged_content) {
+        Ok(()) => {
+            events::emit(ctx, AppEvent::ApplySucceeded { active: active_profile_ids(&config) });
+            Ok(())
         }
-    };
-
-    for profile in config.profiles {
-        if profile.active {
-            merged_content.push_str(&format!("### Profile: {} ###\n", profile.name));
-            merged_content.push_str(&read_profile(&profile.id));
-            merged_content.push_str("\n\n");
+        Err(e) => {
+            events::emit(ctx, AppEvent::ApplyFailed { error: e.clone() });
+            Err(e)
         }
     }
+}
 
-    crate::hosts::save_system_hosts(merged_content)
+#[derive(Debug, Serialize, Clone, Default)]
+pub struct AdoptReport {
+    pub common_restored: bool,
+    pub profiles: Vec<AdoptedProfile>,
+}
+
+#[derive(Debug, Serialize, Clone)]
+pub struct AdoptedProfile {
+    pub id: String,
+    pub name: String,
+    /// false if a profile with this name already existed and was overwritten
+    pub created: bool,
+}
+
+#[tauri::command]
+pub fn adopt_hosts(app: AppHandle) -> Result<AdoptReport, String> {
+    adopt_hosts_internal(&Context::Tauri(&app))
+}
+
+/// Recreates the common config and profiles from the section markers of the current system
+/// hosts file, e.g. after the app data was lost. Recovered profiles are the only active ones.
+pub fn adopt_hosts_internal(ctx: &Context) -> Result<AdoptReport, String> {
+    let content = crate::hosts::get_system_hosts()?;
+    let sections = crate::hosts::parse_generated_hosts(&content)
+        .ok_or("Hosts file was not generated by Hostly (no section markers found)")?;
+
+    let mut report = AdoptReport::default();
+    if let Some(common) = sections.common {
+        save_common_config_internal(ctx, common)?;
+        report.common_restored = true;
+    }
+
+    // A fresh install has no config yet; the first-run sample profiles would only get in the way
+    let mut config = load_config_if_exists(ctx)?.unwrap_or_default();
+    for p in &mut config.profiles {
+        p.active = false;
+    }
+    if sections.profiles.len() > 1 {
+        config.multi_select = true;
+    }
+
+    for (name, body) in sections.profiles {
+        let existing = config.profiles.iter_mut().find(|p| p.name == name);
+        let (id, created) = match existing {
+            Some(p) => {
+                p.active = true;
+                (p.id.clone(), false)
+            }
+            None => {
+                let id = Uuid::new_v4().to_string();
+                config.profiles.push(ProfileMetadata {
+                    id: id.clone(),
+                    name: name.clone(),
+                    active: true,
+                    ..Default::default()
+                });
+                (id, true)
+            }
+        };
+        write_profile_file(ctx, &config, &id, &body)?;
+        report.profiles.push(AdoptedProfile { id, name, created });
+    }
+
+    save_config_internal(ctx, &config)?;
+    Ok(report)
 }
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct FullBackup {
-    version: i32,
-    timestamp: String,
-    config: AppConfig,
+    pub version: i32,
+    pub timestamp: String,
+    pub config: AppConfig,
     // Support both new (Vec) and old (HashMap) formats for compatibility
-    profiles: Option<Vec<ProfileData>>,
-    profiles_content: Option<std::collections::HashMap<String, String>>,
+    pub profiles: Option<Vec<ProfileData>>,
+    pub profiles_content: Option<std::collections::HashMap<String, String>>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Default, clap::ValueEnum)]
+#[serde(rename_all = "snake_case")]
+pub enum ImportStrategy {
+    /// Replace all local profiles and settings with the backup
+    #[default]
+    ReplaceAll,
+    /// Only add profiles whose name does not exist locally
+    AddNew,
+    /// Overwrite local profiles with the same name, add the rest
+    Overwrite,
+    /// Add profiles whose name already exists under a suffixed name
+    KeepBoth,
+}
+
+#[derive(Debug, Serialize, Clone, Default)]
+pub struct ImportReport {
+    pub strategy: ImportStrategy,
+    pub dry_run: bool,
+    pub added: Vec<String>,
+    pub overwritten: Vec<String>,
+    pub renamed: Vec<RenamedProfile>,
+    pub skipped: Vec<String>,
+    /// Local profiles dropped because they are not in the backup (replace all only)
+    pub removed: Vec<String>,
+    pub warnings: Vec<String>,
+}
+
+#[derive(Debug, Serialize, Clone)]
+pub struct RenamedProfile {
+    pub from: String,
+    pub to: String,
+}
+
+/// Profile IDs become file names, so only accept what `Uuid` produces (plus `_`).
+pub fn is_safe_profile_id(id: &str) -> bool {
+    !id.is_empty() && id.len() <= 64 && id.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+}
+
+/// "Dev" -> "Dev (2)", "Dev (3)", ... whichever is free first.
+pub fn unique_profile_name(config: &AppConfig, name: &str) -> String {
+    (2..)
+        .map(|n| format!("{} ({})", name, n))
+        .find(|candidate| !profile_name_taken(config, candidate, None))
+        .unwrap()
+}
+
+/// Checks a backup before anything is written and returns its profile contents by ID.
+fn validate_backup(backup: &FullBackup, warnings: &mut Vec<String>) -> Result<std::collections::HashMap<String, String>, String> {
+    let mut ids = std::collections::HashSet::new();
+    for p in &backup.config.profiles {
+        if !is_safe_profile_id(&p.id) {
+            return Err(format!("Invalid backup: unsafe profile ID {:?} for '{}'", p.id, p.name));
+        }
+        if !ids.insert(p.id.as_str()) {
+            return Err(format!("Invalid backup: duplicate profile ID {}", p.id));
+        }
+    }
+
+    let entries: Vec<(&String, &String)> = match (&backup.profiles, &backup.profiles_content) {
+        (Some(profiles), _) => profiles.iter().map(|p| (&p.id, &p.content)).collect(),
+        (None, Some(map)) => map.iter().collect(),
+        (None, None) => Vec::new(),
+    };
+
+    let mut contents = std::collections::HashMap::new();
+    for (id, content) in entries {
+        if !is_safe_profile_id(id) {
+            return Err(format!("Invalid backup: unsafe profile ID {:?}", id));
+        }
+        if contents.insert(id.clone(), content.clone()).is_some() {
+            return Err(format!("Invalid backup: duplicate content for profile ID {}", id));
+        }
+        if !ids.contains(id.as_str()) {
+            warnings.push(format!("Content for unknown profile {} ignored", id));
+        }
+    }
+    for p in &backup.config.profiles {
+        if !contents.contains_key(&p.id) {
+            warnings.push(format!("Profile '{}' has no content in the backup, imported empty", p.name));
+        }
+    }
+
+    Ok(contents)
 }
 
 #[tauri::command]
-pub fn import_data(app: AppHandle, json_content: String) -> Result<(), String> {
-    import_data_internal(&Context::Tauri(&app), json_content)?;
-    apply_config(app)
+pub fn import_data(
+    app: AppHandle,
+    json_content: String,
+    strategy: Option<ImportStrategy>,
+    dry_run: Option<bool>
+) -> Result<ImportReport, String> {
+    let dry_run = dry_run.unwrap_or(false);
+    let report = import_data_internal(&Context::Tauri(&app), json_content, strategy.unwrap_or_default(), dry_run)?;
+    if !dry_run {
+        apply_config(app)?;
+    }
+    Ok(report)
 }
 
-pub fn import_data_internal(ctx: &Context, json_content: String) -> Result<(), String> {
+pub fn import_data_internal(ctx: &Context, json_content: String, strategy: ImportStrategy, dry_run: bool) -> Result<ImportReport, String> {
     let backup: FullBackup = serde_json::from_str(&json_content).map_err(|e| e.to_string())?;
-    
-    // Reset config
-    save_config_internal(ctx, &backup.config)?;
-    
-    // Save each profile (New Version: Vec<ProfileData>)
-    if let Some(profiles) = backup.profiles {
-        for profile in profiles {
-            save_profile_file_internal(ctx, &profile.id, &profile.content)?;
+    import_backup_internal(ctx, backup, strategy, dry_run)
+}
+
+pub fn import_backup_internal(ctx: &Context, backup: FullBackup, strategy: ImportStrategy, dry_run: bool) -> Result<ImportReport, String> {
+    let mut report = ImportReport { strategy, dry_run, ..Default::default() };
+    let contents = validate_backup(&backup, &mut report.warnings)?;
+    let mut config = load_config_internal(ctx)?;
+    let mut writes: Vec<(String, String)> = Vec::new();
+
+    if strategy == ImportStrategy::ReplaceAll {
+        let collisions = file_name_collisions(&backup.config);
+        if config.storage_layout == StorageLayout::Named && !collisions.is_empty() {
+            return Err(format!("Backup profiles would share a file in the named layout: {}", collisions.join(", ")));
         }
-    } 
-    // Save each profile (Old Version: HashMap<id, content>)
-    else if let Some(profiles_content) = backup.profiles_content {
-        for (id, content) in profiles_content {
-            save_profile_file_internal(ctx, &id, &content)?;
+        for p in &backup.config.profiles {
+            if config.profiles.iter().any(|local| local.id == p.id) {
+                report.overwritten.push(p.name.clone());
+            } else {
+                report.added.push(p.name.clone());
+            }
+            writes.push((p.id.clone(), contents.get(&p.id).cloned().unwrap_or_default()));
         }
+        let removed: Vec<ProfileMetadata> = config.profiles.iter()
+            .filter(|local| !backup.config.profiles.iter().any(|p| p.id == local.id))
+            .cloned()
+            .collect();
+        report.removed = removed.iter().map(|p| p.name.clone()).collect();
+
+        if !dry_run {
+            // Clear out every local file first, otherwise dropped (or, in the named layout,
+            // renamed) profiles would be left behind as orphans
+            for p in &config.profiles {
+                remove_profile_file(ctx, &config, &p.id)?;
+            }
+            let mut new_config = backup.config;
+            // These describe this machine rather than the profiles
+            new_config.storage_layout = config.storage_layout;
+            new_config.proxy = config.proxy.clone();
+            new_config.control_api = config.control_api.clone();
+            for (id, content) in &writes {
+                write_profile_file(ctx, &new_config, id, content)?;
+            }
+            save_config_internal(ctx, &new_config)?;
+        }
+        return Ok(report);
     }
-    
-    Ok(())
+
+    let original = config.clone();
+    let mut replaced: Vec<String> = Vec::new();
+    for meta in &backup.config.profiles {
+        let content = contents.get(&meta.id).cloned().unwrap_or_default();
+        let existing = config.profiles.iter().position(|p| p.name == meta.name);
+
+        match (strategy, existing) {
+            (ImportStrategy::AddNew, Some(_)) => {
+                report.skipped.push(meta.name.clone());
+            }
+            (ImportStrategy::Overwrite, Some(idx)) => {
+                // Keep the local ID and on/off state, take everything else from the backup
+                let local = &mut config.profiles[idx];
+                *local = ProfileMetadata { id: local.id.clone(), active: local.active, ..meta.clone() };
+                replaced.push(local.id.clone());
+                writes.push((local.id.clone(), content));
+                report.overwritten.push(meta.name.clone());
+            }
+            (_, existing) => {
+                let name = match existing {
+                    None if !profile_name_taken(&config, &meta.name, None) => meta.name.clone(),
+                    _ => unique_profile_name(&config, &meta.name),
+                };
+                // The backup ID may belong to a different local profile (e.g. renamed since)
+                let id = if config.profiles.iter().any(|p| p.id == meta.id) {
+                    Uuid::new_v4().to_string()
+                } else {
+                    meta.id.clone()
+                };
+
+                if name != meta.name {
+                    report.renamed.push(RenamedProfile { from: meta.name.clone(), to: name.clone() });
+                } else {
+                    report.added.push(name.clone());
+                }
+                config.profiles.push(ProfileMetadata { id: id.clone(), name, active: false, ..meta.clone() });
+                writes.push((id, content));
+            }
+        }
+    }
+
+    if !dry_run {
+        // In the named layout an overwritten profile may move to the backup's folder
+        for id in &replaced {
+            remove_profile_file(ctx, &original, id)?;
+        }
+        for (id, content) in &writes {
+            write_profile_file(ctx, &config, id, content)?;
+        }
+        save_config_internal(ctx, &config)?;
+    }
+    Ok(report)
 }
 
 #[tauri::command]
@@ -493,9 +1085,19 @@ pub fn export_data(app: AppHandle) -> Result<String, String> {
     export_data_internal(&Context::Tauri(&app))
 }
 
+/// Config as written into backups, without the proxy password.
+pub fn exportable_config(mut config: AppConfig) -> AppConfig {
+    config.proxy.password = None;
+    config
+}
+
 pub fn export_data_internal(ctx: &Context) -> Result<String, String> {
-    let config = load_config_internal(ctx)?;
-    let profiles = list_profiles_internal(ctx)?;
+    let config = exportable_config(load_config_internal(ctx)?);
+    // Refresh state is already in the config, no need to repeat it per profile
+    let profiles = list_profiles_internal(ctx)?
+        .into_iter()
+        .map(|p| ProfileData { remote: None, ..p })
+        .collect();
     
     let backup = FullBackup {
         version: 2,
@@ -546,245 +1148,192 @@ pub fn upsert_profile_internal(ctx: &Context, name: String, content: String) ->
     }
 }
 
-#[tauri::command]
-pub fn import_switchhosts(app: AppHandle, json_content: String) -> Result<usize, String> {
-    let ctx = Context::Tauri(&app);
-    let count = import_switchhosts_internal(&ctx, json_content)?;
-    apply_config(app)?;
-    Ok(count)
-}
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-pub fn import_switchhosts_internal(ctx: &Context, json_content: String) -> Result<usize, String> {
-    let raw: serde_json::Value = serde_json::from_str(&json_content).map_err(|e| format!("Invalid JSON: {}", e))?;
-    
-    // SwitchHosts v4+ format: data.list.tree (structure) + data.collection.hosts.data (content)
-    if let Some(data) = raw.get("data") {
-        let mut content_map = std::collections::HashMap::new();
-        
-        // Build ID -> Content map
-        if let Some(hosts_data) = data.get("collection")
-            .and_then(|c| c.get("hosts"))
-            .and_then(|h| h.get("data"))
-            .and_then(|d| d.as_array()) 
-        {
-            for h in hosts_data {
-                if let (Some(id), Some(content)) = (h.get("id").and_then(|v| v.as_str()), h.get("content").and_then(|v| v.as_str())) {
-                    content_map.insert(id, content);
-                }
-            }
-        }
-
-        // Traverse tree
-        if let Some(tree) = data.get("list").and_then(|l| l.get("tree")).and_then(|t| t.as_array()) {
-            let mut count = 0;
-            parse_switchhosts_v4_tree_internal(ctx, tree, &content_map, &mut count)?;
-            return Ok(count);
+    fn with_names(names: &[&str]) -> AppConfig {
+        AppConfig {
+            profiles: names.iter().enumerate()
+                .map(|(i, n)| ProfileMetadata { id: i.to_string(), name: n.to_string(), ..Default::default() })
+                .collect(),
+            ..Default::default()
         }
     }
 
-    // Fallback to simpler format (v1-v3 or simpler exports)
-    let list = if let Some(l) = raw.get("list") {
-        l.as_array().ok_or("Invalid SwitchHosts format: 'list' is not an array")?
-    } else if raw.is_array() {
-        raw.as_array().unwrap()
-    } else {
-        return Err("Invalid SwitchHosts format: Expected SH v4 structure or a simple array".to_string());
-    };
+    #[test]
+    fn file_stem_replaces_unsafe_characters() {
+        assert_eq!(profile_file_stem("a/b\\c:d*e?f\"g<h>i|j"), "a_b_c_d_e_f_g_h_i_j");
+        assert_eq!(profile_file_stem("tab\there"), "tab_here");
+        assert_eq!(profile_file_stem("开发 环境"), "开发 环境");
+        assert_eq!(profile_file_stem(".."), "__");
+        assert_eq!(profile_file_stem("."), "_");
+        assert_eq!(profile_file_stem("v1.2"), "v1.2");
+    }
 
-    let mut count = 0;
-    parse_switchhosts_items_internal(ctx, list, &mut count)?;
+    #[test]
+    fn same_file_name_ignores_case_and_sanitizing() {
+        assert!(same_file_name("Dev", "dev"));
+        assert!(same_file_name("a/b", "a:b"));
+        assert!(!same_file_name("Dev", "Dev2"));
+    }
 
-    Ok(count)
-}
+    #[test]
+    fn colliding_names_are_rejected() {
+        let config = with_names(&["Dev", "a/b"]);
+        assert!(check_profile_name(&config, "Dev", None).unwrap_err().contains("already exists"));
+        assert!(check_profile_name(&config, "DEV", None).unwrap_err().contains("'Dev'"));
+        assert!(check_profile_name(&config, "a|b", None).is_err());
+        assert!(check_profile_name(&config, "dev", Some("0")).is_ok());
+        assert!(check_profile_name(&config, "Test", None).is_ok());
+    }
 
-fn parse_switchhosts_v4_tree_internal(
-    ctx: &Context, 
-    items: &Vec<serde_json::Value>, 
-    content_map: &std::collections::HashMap<&str, &str>, 
-    count: &mut usize
-) -> Result<(), String> {
-    for item in items {
-        let title = item.get("title").and_then(|v| v.as_str()).unwrap_or("Unknown");
-        let item_type = item.get("type").and_then(|v| v.as_str()).unwrap_or("local");
-        let id = item.get("id").and_then(|v| v.as_str()).unwrap_or("");
+    #[test]
+    fn unique_name_avoids_file_collisions() {
+        let config = with_names(&["Dev", "dev (2)"]);
+        assert_eq!(unique_profile_name(&config, "Dev"), "Dev (3)");
+        assert_eq!(file_name_collisions(&config), Vec::<String>::new());
+        assert_eq!(file_name_collisions(&with_names(&["Dev", "Test", "DEV"])), ["'Dev' / 'DEV'"]);
+    }
 
-        if item_type == "folder" {
-            if let Some(children) = item.get("children").and_then(|c| c.as_array()) {
-                parse_switchhosts_v4_tree_internal(ctx, children, content_map, count)?;
-            }
-        } else {
-            // Find content in map or item itself
-            let content = content_map.get(id).map(|c| *c).or_else(|| item.get("content").and_then(|v| v.as_str())).unwrap_or("");
-            upsert_profile_internal(ctx, title.to_string(), content.to_string())?;
-            *count += 1;
+    /// A store holding profiles of (id, name, content); the first one is active.
+    fn store(dir: &std::path::Path, profiles: &[(&str, &str, &str)]) -> Context<'static> {
+        let ctx = Context::Dir(dir.to_path_buf());
+        let mut config = with_names(&[]);
+        for (i, (id, name, content)) in profiles.iter().enumerate() {
+            config.profiles.push(ProfileMetadata { id: id.to_string(), name: name.to_string(), active: i == 0, ..Default::default() });
+            write_profile_file(&ctx, &config, id, content).unwrap();
         }
+        save_config_internal(&ctx, &config).unwrap();
+        ctx
     }
-    Ok(())
-}
 
-fn parse_switchhosts_items_internal(ctx: &Context, items: &Vec<serde_json::Value>, count: &mut usize) -> Result<(), String> {
-    for item in items {
-        let title = item.get("title").and_then(|v| v.as_str()).unwrap_or("Unknown");
-        let folder = item.get("folder").and_then(|v| v.as_bool())
-            .or_else(|| item.get("type").and_then(|v| Some(v.as_str() == Some("folder"))))
-            .unwrap_or(false);
-        
-        if folder {
-            if let Some(children) = item.get("children").and_then(|c| c.as_array()) {
-                parse_switchhosts_items_internal(ctx, children, count)?;
-            }
-        } else {
-            let content = item.get("content").and_then(|v| v.as_str()).unwrap_or("");
-            upsert_profile_internal(ctx, title.to_string(), content.to_string())?;
-            *count += 1;
+    fn backup(profiles: &[(&str, &str, &str)]) -> FullBackup {
+        let names: Vec<&str> = profiles.iter().map(|(_, name, _)| *name).collect();
+        let mut config = with_names(&names);
+        for (p, (id, _, _)) in config.profiles.iter_mut().zip(profiles) {
+            p.id = id.to_string();
+        }
+        FullBackup {
+            version: 2,
+            timestamp: String::new(),
+            config,
+            profiles: None,
+            profiles_content: Some(profiles.iter().map(|(id, _, content)| (id.to_string(), content.to_string())).collect()),
         }
     }
 
-    Ok(())
-}
+    /// (name, content, active) of every profile, in config order.
+    fn profiles(ctx: &Context) -> Vec<(String, String, bool)> {
+        let config = load_config_internal(ctx).unwrap();
+        config.profiles.iter()
+            .map(|p| (p.name.clone(), read_profile_file(ctx, &config, &p.id).unwrap(), p.active))
+            .collect()
+    }
 
-pub fn check_auto_updates(app: &AppHandle) {
-    let ctx = Context::Tauri(app);
-    // Silent check, allow errors to just print to stderr
-    if let Ok(config) = load_config_internal(&ctx) {
-        let now = chrono::Local::now();
-        let mut needs_save = false;
-        
-        // Collect IDs to update to avoid borrow checker issues with iterating & mutating config
-        let mut updates_needed = Vec::new();
-
-        for p in &config.profiles {
-            if let (Some(_url), Some(interval), Some(last_update_str)) = (&p.url, p.update_interval, &p.last_update) {
-                if interval > 0 {
-                    if let Ok(last_update) = chrono::DateTime::parse_from_rfc3339(last_update_str) {
-                        let diff = now.signed_duration_since(last_update);
-                        if diff.num_seconds() >= interval as i64 {
-                            updates_needed.push(p.id.clone());
-                        }
-                    }
-                }
-            } else if let (Some(_url), Some(interval), None) = (&p.url, p.update_interval, &p.last_update) {
-                // Never updated, but has interval -> update now
-                 if interval > 0 {
-                    updates_needed.push(p.id.clone());
-                 }
+    fn files(dir: &std::path::Path) -> std::collections::BTreeMap<PathBuf, Vec<u8>> {
+        let mut files = std::collections::BTreeMap::new();
+        for entry in fs::read_dir(dir).unwrap().flatten() {
+            let path = entry.path();
+            if path.is_dir() {
+                files.extend(self::files(&path));
+            } else {
+                files.insert(path.clone(), fs::read(&path).unwrap());
             }
         }
-        
-        for id in updates_needed {
-            println!("Auto-updating profile {}...", id);
-            if let Err(e) = trigger_profile_update_internal(&ctx, &id) {
-                eprintln!("Failed to auto-update {}: {}", id, e);
-            }
-            // re-application is handled inside trigger_profile_update_internal? 
-            // implementation_plan said Trigger triggers re-apply. 
-            // Actually `trigger_profile_update` command does, but `internal` does NOT re-apply.
-            // We should reload config to check if active and apply if needed?
-            // checking internal implementation...
-            // `trigger_profile_update_internal` saves file and updates timestamp in config.
-            // It does NOT call apply_config.
-            // So we need to do it here if any update happened.
-            needs_save = true;
-        }
-
-        if needs_save {
-             // Re-apply config if any active profile was updated
-             // Optimization: check if any updated profile was active
-             // For now, just apply to be safe
-             let _ = apply_config_internal(&ctx);
-        }
+        files
     }
-}
 
-#[tauri::command]
-pub fn update_remote_config(
-    app: AppHandle,
-    id: String,
-    url: Option<String>,
-    update_interval: Option<u64>
-) -> Result<(), String> {
-    let ctx = Context::Tauri(&app);
-    let mut config = load_config_internal(&ctx)?;
-    
-    if let Some(p) = config.profiles.iter_mut().find(|p| p.id == id) {
-        p.url = url;
-        p.update_interval = update_interval;
-    } else {
-        return Err("Profile not found".to_string());
+    fn local(dir: &std::path::Path) -> Context<'static> {
+        store(dir, &[("a", "Dev", "1.1.1.1 dev\n"), ("b", "Test", "2.2.2.2 test\n")])
     }
 
-    save_config_internal(&ctx, &config)
-}
-
-#[tauri::command]
-pub fn trigger_profile_update(app: AppHandle, id: String) -> Result<(), String> {
-    let ctx = Context::Tauri(&app);
-    trigger_profile_update_internal(&ctx, &id)?;
-    // If active, re-apply
-    let config = load_config_internal(&ctx)?;
-    if config.profiles.iter().any(|p| p.id == id && p.active) {
-        apply_config(app)?;
+    fn incoming() -> FullBackup {
+        backup(&[("a", "Dev", "9.9.9.9 dev\n"), ("c", "Prod", "3.3.3.3 prod\n")])
     }
-    Ok(())
-}
 
-pub fn trigger_profile_update_internal(ctx: &Context, id: &str) -> Result<(), String> {
-    let mut config = load_config_internal(ctx)?;
-    
-    let (url, name) = if let Some(p) = config.profiles.iter().find(|p| p.id == id) {
-        (p.url.clone(), p.name.clone())
-    } else {
-        return Err("Profile not found".to_string());
-    };
-
-    let url = url.ok_or("Profile is not a remote profile (no URL)")?;
-    
-    // Download
-    println!("Downloading profile '{}' from '{}'...", name, url);
-    let content = download_text(&url)?;
-
-    // Save Content
-    save_profile_file_internal(ctx, id, &content)?;
+    #[test]
+    fn replace_all_mirrors_the_backup() {
+        let dir = tempfile::tempdir().unwrap();
+        let ctx = local(dir.path());
+        let report = import_backup_internal(&ctx, incoming(), ImportStrategy::ReplaceAll, false).unwrap();
+        assert_eq!((report.overwritten, report.added, report.removed), (vec!["Dev".to_string()], vec!["Prod".to_string()], vec!["Test".to_string()]));
+        assert_eq!(profiles(&ctx), [
+            ("Dev".to_string(), "9.9.9.9 dev\n".to_string(), false),
+            ("Prod".to_string(), "3.3.3.3 prod\n".to_string(), false),
+        ]);
+        assert!(!dir.path().join("profiles/b.txt").exists());
+    }
 
-    // Update Timestamp
-    if let Some(p) = config.profiles.iter_mut().find(|p| p.id == id) {
-        p.last_update = Some(chrono::Local::now().to_rfc3339());
+    #[test]
+    fn add_new_skips_existing_names() {
+        let dir = tempfile::tempdir().unwrap();
+        let ctx = local(dir.path());
+        let report = import_backup_internal(&ctx, incoming(), ImportStrategy::AddNew, false).unwrap();
+        assert_eq!((report.skipped, report.added), (vec!["Dev".to_string()], vec!["Prod".to_string()]));
+        let names: Vec<String> = profiles(&ctx).into_iter().map(|(name, _, _)| name).collect();
+        assert_eq!(names, ["Dev", "Test", "Prod"]);
+        assert_eq!(profiles(&ctx)[0].1, "1.1.1.1 dev\n");
     }
-    save_config_internal(ctx, &config)?;
-    
-    Ok(())
-}
 
-fn download_text(urls_str: &str) -> Result<String, String> {
-    let mut combined_content = String::new();
-    let urls: Vec<&str> = urls_str.lines().map(|s| s.trim()).filter(|s| !s.is_empty()).collect();
+    #[test]
+    fn overwrite_keeps_the_local_id_and_state() {
+        let dir = tempfile::tempdir().unwrap();
+        let ctx = local(dir.path());
+        // Same name under another ID, e.g. a backup from a different machine
+        let backup = backup(&[("x", "Dev", "9.9.9.9 dev\n")]);
+        let report = import_backup_internal(&ctx, backup, ImportStrategy::Overwrite, false).unwrap();
+        assert_eq!(report.overwritten, ["Dev"]);
+        let config = load_config_internal(&ctx).unwrap();
+        assert_eq!(config.profiles[0].id, "a");
+        assert_eq!(profiles(&ctx)[0], ("Dev".to_string(), "9.9.9.9 dev\n".to_string(), true));
+        assert_eq!(config.profiles.len(), 2);
+    }
 
-    if urls.is_empty() {
-        return Err("No valid URLs provided".to_string());
+    #[test]
+    fn keep_both_renames_and_reassigns_taken_ids() {
+        let dir = tempfile::tempdir().unwrap();
+        let ctx = local(dir.path());
+        let report = import_backup_internal(&ctx, incoming(), ImportStrategy::KeepBoth, false).unwrap();
+        assert_eq!(report.renamed.iter().map(|r| (r.from.as_str(), r.to.as_str())).collect::<Vec<_>>(), [("Dev", "Dev (2)")]);
+        assert_eq!(report.added, ["Prod"]);
+
+        let config = load_config_internal(&ctx).unwrap();
+        let copy = config.profiles.iter().find(|p| p.name == "Dev (2)").unwrap();
+        assert_ne!(copy.id, "a");
+        assert!(!copy.active);
+        assert_eq!(read_profile_file(&ctx, &config, &copy.id).unwrap(), "9.9.9.9 dev\n");
+        assert_eq!(read_profile_file(&ctx, &config, "a").unwrap(), "1.1.1.1 dev\n");
+        assert_eq!(config.profiles.iter().find(|p| p.name == "Prod").unwrap().id, "c");
     }
 
-    for url in urls {
-        let content = download_single_url(url)?;
-        if !combined_content.is_empty() {
-            combined_content.push_str("\n\n");
+    #[test]
+    fn dry_runs_leave_the_store_alone() {
+        let dir = tempfile::tempdir().unwrap();
+        let ctx = local(dir.path());
+        let before = files(dir.path());
+        for strategy in [ImportStrategy::ReplaceAll, ImportStrategy::AddNew, ImportStrategy::Overwrite, ImportStrategy::KeepBoth] {
+            let report = import_backup_internal(&ctx, incoming(), strategy, true).unwrap();
+            assert!(report.dry_run);
+            assert!(!report.added.is_empty());
+            assert_eq!(files(dir.path()), before, "{:?} wrote to the store", strategy);
         }
-        combined_content.push_str(&format!("# Source: {}\n", url));
-        combined_content.push_str(&content);
     }
 
-    Ok(combined_content)
-}
-
-fn download_single_url(url: &str) -> Result<String, String> {
-    let response = minreq::get(url)
-        .with_timeout(10)
-        .send()
-        .map_err(|e| format!("Network error downloading {}: {}", url, e))?;
-        
-    if response.status_code >= 200 && response.status_code < 300 {
-        response.as_str().map(|s| s.to_string()).map_err(|e| format!("Invalid text encoding from {}: {}", url, e))
-    } else {
-        Err(format!("HTTP Error {} from {}", response.status_code, url))
+    #[test]
+    fn unsafe_backup_ids_are_rejected() {
+        let mut warnings = Vec::new();
+        let err = validate_backup(&backup(&[("../x", "Evil", "")]), &mut warnings).unwrap_err();
+        assert!(err.contains("unsafe profile ID"));
+
+        let mut content_only = backup(&[("a", "Dev", "")]);
+        content_only.profiles_content.as_mut().unwrap().insert("../../etc/x".to_string(), String::new());
+        assert!(validate_backup(&content_only, &mut warnings).is_err());
+        assert!(validate_backup(&backup(&[("a", "Dev", ""), ("a", "Test", "")]), &mut warnings).unwrap_err().contains("duplicate"));
+
+        let dir = tempfile::tempdir().unwrap();
+        let ctx = local(dir.path());
+        assert!(import_backup_internal(&ctx, backup(&[("../x", "Evil", "")]), ImportStrategy::AddNew, false).is_err());
+        assert_eq!(profiles(&ctx).len(), 2);
     }
 }
-
@@ -0,0 +1,458 @@
+use crate::storage::{self, Context, ProfileMetadata};
+use serde::Serialize;
+use serde_json::Value;
+use std::collections::HashMap;
+use tauri::AppHandle;
+use uuid::Uuid;
+
+#[derive(Debug, Serialize, Clone, Default)]
+pub struct SwitchHostsImportReport {
+    pub imported: Vec<ImportedItem>,
+    pub skipped: Vec<SkippedItem>,
+    pub multi_select: bool,
+    /// The backup was exported by Hostly and carried the common config
+    #[serde(default)]
+    pub common_restored: bool,
+}
+
+#[derive(Debug, Serialize, Clone)]
+pub struct ImportedItem {
+    pub id: String,
+    /// Title in the SwitchHosts backup
+    pub title: String,
+    /// Name of the Hostly profile, differs from `title` when it had to be renamed
+    pub name: String,
+    pub renamed: bool,
+    pub folder: Option<String>,
+    pub remote: bool,
+    pub active: bool,
+}
+
+#[derive(Debug, Serialize, Clone)]
+pub struct SkippedItem {
+    pub title: String,
+    pub reason: String,
+}
+
+/// A leaf of the SwitchHosts tree, flattened but with its folder path kept.
+struct Entry {
+    id: String,
+    title: String,
+    folder: Option<String>,
+    content: String,
+    url: Option<String>,
+    update_interval: Option<u64>,
+    last_update: Option<String>,
+    on: bool,
+}
+
+#[tauri::command]
+pub fn import_switchhosts(app: AppHandle, json_content: String) -> Result<SwitchHostsImportReport, String> {
+    let ctx = Context::Tauri(&app);
+    let report = import_switchhosts_internal(&ctx, json_content)?;
+    storage::apply_config(app)?;
+    Ok(report)
+}
+
+pub fn import_switchhosts_internal(ctx: &Context, json_content: String) -> Result<SwitchHostsImportReport, String> {
+    let raw: Value = serde_json::from_str(&json_content).map_err(|e| format!("Invalid JSON: {}", e))?;
+
+    let mut entries = Vec::new();
+    let mut report = SwitchHostsImportReport::default();
+
+    // SwitchHosts v4+ format: data.list.tree (structure) + data.collection.hosts.data (content)
+    let tree = raw.get("data").and_then(|d| d.get("list")).and_then(|l| l.get("tree")).and_then(|t| t.as_array());
+    if let (Some(data), Some(tree)) = (raw.get("data"), tree) {
+        let mut content_map = HashMap::new();
+
+        // Build ID -> Content map
+        if let Some(hosts_data) = data.get("collection")
+            .and_then(|c| c.get("hosts"))
+            .and_then(|h| h.get("data"))
+            .and_then(|d| d.as_array())
+        {
+            for h in hosts_data {
+                if let (Some(id), Some(content)) = (h.get("id").and_then(|v| v.as_str()), h.get("content").and_then(|v| v.as_str())) {
+                    content_map.insert(id, content);
+                }
+            }
+        }
+
+        collect_entries(tree, &content_map, None, &mut entries, &mut report.skipped);
+    } else {
+        // Fallback to simpler format (v1-v3 or simpler exports)
+        let list = if let Some(l) = raw.get("list") {
+            l.as_array().ok_or("Invalid SwitchHosts format: 'list' is not an array")?
+        } else if let Some(l) = raw.as_array() {
+            l
+        } else {
+            return Err("Invalid SwitchHosts format: Expected SH v4 structure or a simple array".to_string());
+        };
+
+        collect_entries(list, &HashMap::new(), None, &mut entries, &mut report.skipped);
+    }
+
+    let mut config = storage::load_config_internal(ctx)?;
+
+    // SwitchHosts keeps its choice mode in the app config, which only some exports carry
+    let choice_mode = ["/data/dict/config/choice_mode", "/data/config/choice_mode", "/config/choice_mode"]
+        .iter()
+        .find_map(|path| raw.pointer(path).and_then(|v| v.as_u64()));
+    // The common config item of a Hostly export is always on, it says nothing about profiles
+    let profiles_on = entries.iter().filter(|e| e.on && e.id != COMMON_ITEM_ID).count();
+    let multi_select = match choice_mode {
+        Some(mode) => mode == 2,
+        None => profiles_on > 1,
+    };
+
+    let any_on = profiles_on > 0;
+    if any_on {
+        config.multi_select = multi_select;
+        if !multi_select {
+            for p in &mut config.profiles {
+                p.active = false;
+            }
+        }
+    }
+    report.multi_select = config.multi_select;
+
+    let mut activated = false;
+    for entry in entries {
+        if entry.id == COMMON_ITEM_ID {
+            storage::save_common_config_internal(ctx, entry.content)?;
+            report.common_restored = true;
+            continue;
+        }
+        let existing = config.profiles.iter().find(|p| p.name == entry.title);
+        let (name, renamed) = match existing {
+            Some(p) if p.url == entry.url && storage::read_profile_file_internal(ctx, &p.id).unwrap_or_default() == entry.content => {
+                report.skipped.push(SkippedItem {
+                    title: entry.title,
+                    reason: "Identical profile already exists".to_string(),
+                });
+                continue;
+            }
+            Some(_) => (storage::unique_profile_name(&config, &entry.title), true),
+            None if !storage::profile_name_taken(&config, &entry.title, None) => (entry.title.clone(), false),
+            None => (storage::unique_profile_name(&config, &entry.title), true),
+        };
+
+        // In single-select mode only the first switched-on item can stay on
+        let active = entry.on && (config.multi_select || !activated);
+        activated |= active;
+
+        let id = Uuid::new_v4().to_string();
+        config.profiles.push(ProfileMetadata {
+            id: id.clone(),
+            name: name.clone(),
+            active,
+            url: entry.url.clone(),
+            last_update: entry.last_update,
+            update_interval: entry.update_interval,
+            folder: entry.folder.clone(),
+            ..Default::default()
+        });
+        storage::write_profile_file(ctx, &config, &id, &entry.content)?;
+
+        report.imported.push(ImportedItem {
+            id,
+            title: entry.title,
+            name,
+            renamed,
+            folder: entry.folder,
+            remote: entry.url.is_some(),
+            active,
+        });
+    }
+
+    storage::save_config_internal(ctx, &config)?;
+    Ok(report)
+}
+
+fn collect_entries(
+    items: &[Value],
+    content_map: &HashMap<&str, &str>,
+    folder: Option<&str>,
+    entries: &mut Vec<Entry>,
+    skipped: &mut Vec<SkippedItem>,
+) {
+    for item in items {
+        let title = item.get("title").and_then(|v| v.as_str()).unwrap_or("Unknown").to_string();
+        let id = item.get("id").and_then(|v| v.as_str()).unwrap_or("");
+        // v4 uses `type`, v3 used `where` (plus a `folder` flag in some builds)
+        let item_type = item.get("type").or_else(|| item.get("where")).and_then(|v| v.as_str())
+            .unwrap_or(if item.get("folder").and_then(|v| v.as_bool()).unwrap_or(false) { "folder" } else { "local" });
+
+        let content_of = |id: &str, item: &Value| -> String {
+            content_map.get(id).copied()
+                .or_else(|| item.get("content").and_then(|v| v.as_str()))
+                .unwrap_or("")
+                .to_string()
+        };
+
+        let content = match item_type {
+            "folder" => {
+                let path = match folder {
+                    Some(parent) => format!("{}/{}", parent, title.replace('/', "-")),
+                    None => title.replace('/', "-"),
+                };
+                if let Some(children) = item.get("children").and_then(|c| c.as_array()) {
+                    collect_entries(children, content_map, Some(&path), entries, skipped);
+                }
+                continue;
+            }
+            "local" | "remote" => content_of(id, item),
+            "group" => {
+                // Hostly has no groups, so freeze the group into the combined content of its members
+                let Some(include) = item.get("include").and_then(|v| v.as_array()) else {
+                    skipped.push(SkippedItem { title, reason: "Group without members".to_string() });
+                    continue;
+                };
+                include.iter()
+                    .filter_map(|v| v.as_str())
+                    .filter_map(|member| content_map.get(member).copied())
+                    .collect::<Vec<_>>()
+                    .join("\n\n")
+            }
+            other => {
+                skipped.push(SkippedItem { title, reason: format!("Unsupported item type '{}'", other) });
+                continue;
+            }
+        };
+
+        let url = if item_type == "remote" {
+            item.get("url").and_then(|v| v.as_str()).map(|s| s.trim().to_string()).filter(|s| !s.is_empty())
+        } else {
+            None
+        };
+        let update_interval = url.as_ref().and_then(|_| item.get("refresh_interval")).and_then(|v| v.as_u64()).filter(|i| *i > 0);
+        let last_update = url.as_ref()
+            .and_then(|_| item.get("last_refresh_ms"))
+            .and_then(|v| v.as_i64())
+            .and_then(chrono::DateTime::from_timestamp_millis)
+            .map(|t| t.with_timezone(&chrono::Local).to_rfc3339());
+
+        entries.push(Entry {
+            id: id.to_string(),
+            title,
+            folder: folder.map(|f| f.to_string()),
+            content,
+            url,
+            update_interval,
+            last_update,
+            on: item.get("on").and_then(|v| v.as_bool()).unwrap_or(false),
+        });
+    }
+}
+
+/// SwitchHosts version written into exported backups; v4 is the first with the list/collection layout.
+const SWITCHHOSTS_EXPORT_VERSION: [u32; 4] = [4, 2, 0, 0];
+/// Id of the item carrying the common config in exported backups, so importing the backup
+/// back into Hostly restores it as the common config instead of a profile.
+const COMMON_ITEM_ID: &str = "hostly-common-config";
+
+#[derive(Debug, Serialize, Clone)]
+pub struct SwitchHostsExport {
+    pub json: String,
+    /// Settings SwitchHosts can't represent and that were dropped
+    pub warnings: Vec<String>,
+}
+
+#[tauri::command]
+pub fn export_switchhosts(app: AppHandle) -> Result<SwitchHostsExport, String> {
+    export_switchhosts_internal(&Context::Tauri(&app))
+}
+
+/// Builds a SwitchHosts v4 backup (`data.list.tree` + `data.collection.hosts.data`) from the
+/// current profiles. The mapping loses information:
+/// - SwitchHosts has no common config; it becomes a local item that is always switched on
+///   (only Hostly turns it back into the common config on import)
+/// - SwitchHosts takes one URL per remote item, only the first source is exported
+/// - source formats, transform rules, verification and request settings are dropped
+pub fn export_switchhosts_internal(ctx: &Context) -> Result<SwitchHostsExport, String> {
+    let config = storage::load_config_internal(ctx)?;
+    let common = storage::load_common_config_internal(ctx).unwrap_or_default();
+
+    let mut tree: Vec<Value> = Vec::new();
+    let mut hosts_data: Vec<Value> = Vec::new();
+    let mut warnings = Vec::new();
+
+    if !common.trim().is_empty() {
+        tree.push(serde_json::json!({ "id": COMMON_ITEM_ID, "title": "Common Config (Hostly)", "type": "local", "on": true }));
+        hosts_data.push(serde_json::json!({ "id": COMMON_ITEM_ID, "content": common }));
+    }
+
+    for p in &config.profiles {
+        let content = storage::read_profile_file_internal(ctx, &p.id)?;
+        let mut item = serde_json::json!({ "id": p.id, "title": p.name, "type": "local", "on": p.active });
+        let urls: Vec<&str> = p.url.as_deref().unwrap_or("").lines().map(str::trim).filter(|u| !u.is_empty()).collect();
+        if let Some(url) = urls.first() {
+            if urls.len() > 1 {
+                warnings.push(format!("'{}' has {} source URLs, SwitchHosts takes one; only {} was exported", p.name, urls.len(), crate::remote::redact_url(url)));
+            }
+            item["type"] = "remote".into();
+            item["url"] = (*url).into();
+            item["refresh_interval"] = p.update_interval.unwrap_or(0).into();
+            if let Some(last) = p.last_update.as_deref().and_then(|t| chrono::DateTime::parse_from_rfc3339(t).ok()) {
+                item["last_refresh"] = last.format("%Y-%m-%d %H:%M:%S").to_string().into();
+                item["last_refresh_ms"] = last.timestamp_millis().into();
+            }
+        }
+        hosts_data.push(serde_json::json!({ "id": p.id, "content": content }));
+
+        let folder = p.folder.as_deref().map(storage::normalize_folder).unwrap_or_default();
+        insert_into_folder(&mut tree, &folder, item);
+    }
+
+    let backup = serde_json::json!({
+        "version": SWITCHHOSTS_EXPORT_VERSION,
+        "data": {
+            "list": { "tree": tree, "trashcan": [] },
+            "collection": { "hosts": { "meta": {}, "data": hosts_data } },
+            "dict": { "meta": {}, "config": { "choice_mode": if config.multi_select { 2 } else { 1 } } },
+        },
+    });
+
+    let json = serde_json::to_string_pretty(&backup).map_err(|e| e.to_string())?;
+    Ok(SwitchHostsExport { json, warnings })
+}
+
+/// Walks (and creates) the folder items for `folder` ("A/B") and appends `item` to the innermost one.
+fn insert_into_folder(tree: &mut Vec<Value>, folder: &str, item: Value) {
+    if folder.is_empty() {
+        tree.push(item);
+        return;
+    }
+    let (head, rest) = folder.split_once('/').unwrap_or((folder, ""));
+
+    let existing = tree.iter().position(|v| {
+        v.get("type").and_then(|t| t.as_str()) == Some("folder") && v.get("title").and_then(|t| t.as_str()) == Some(head)
+    });
+    let idx = existing.unwrap_or_else(|| {
+        tree.push(serde_json::json!({
+            "id": Uuid::new_v4().to_string(),
+            "title": head,
+            "type": "folder",
+            "children": [],
+        }));
+        tree.len() - 1
+    });
+
+    if let Some(children) = tree[idx].get_mut("children").and_then(|c| c.as_array_mut()) {
+        insert_into_folder(children, rest, item);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn collects_folders_remotes_and_groups() {
+        let tree = vec![
+            json!({ "id": "a", "title": "Dev", "type": "local", "on": true }),
+            json!({ "id": "f", "title": "Work/Team", "type": "folder", "children": [
+                json!({ "id": "r", "title": "Ads", "type": "remote", "url": " https://example.com/ads ", "refresh_interval": 3600 }),
+            ] }),
+            json!({ "id": "g", "title": "Both", "type": "group", "include": ["a", "r"] }),
+            json!({ "id": "x", "title": "Odd", "type": "weird" }),
+        ];
+        let content: HashMap<&str, &str> = [("a", "1.1.1.1 a"), ("r", "0.0.0.0 ads")].into_iter().collect();
+        let mut entries = Vec::new();
+        let mut skipped = Vec::new();
+        collect_entries(&tree, &content, None, &mut entries, &mut skipped);
+
+        let titles: Vec<&str> = entries.iter().map(|e| e.title.as_str()).collect();
+        assert_eq!(titles, ["Dev", "Ads", "Both"]);
+        assert!(entries[0].on);
+        assert_eq!(entries[1].folder.as_deref(), Some("Work-Team"));
+        assert_eq!(entries[1].url.as_deref(), Some("https://example.com/ads"));
+        assert_eq!(entries[1].update_interval, Some(3600));
+        assert_eq!(entries[2].content, "1.1.1.1 a\n\n0.0.0.0 ads");
+        assert_eq!(skipped.len(), 1);
+        assert_eq!(skipped[0].title, "Odd");
+    }
+
+    #[test]
+    fn v3_items_use_where() {
+        let tree = vec![json!({ "title": "Old", "where": "local", "content": "127.0.0.1 old" })];
+        let mut entries = Vec::new();
+        collect_entries(&tree, &HashMap::new(), None, &mut entries, &mut Vec::new());
+        assert_eq!(entries[0].content, "127.0.0.1 old");
+        assert!(entries[0].url.is_none());
+    }
+
+    #[test]
+    fn nested_folders_are_created_once() {
+        let mut tree = Vec::new();
+        insert_into_folder(&mut tree, "A/B", json!({ "id": "1" }));
+        insert_into_folder(&mut tree, "A", json!({ "id": "2" }));
+        insert_into_folder(&mut tree, "", json!({ "id": "3" }));
+        assert_eq!(tree.len(), 2);
+        let a = tree[0]["children"].as_array().unwrap();
+        assert_eq!(a.len(), 2);
+        assert_eq!(a[0]["title"], "B");
+        assert_eq!(a[0]["children"][0]["id"], "1");
+        assert_eq!(a[1]["id"], "2");
+        assert_eq!(tree[1]["id"], "3");
+    }
+
+    fn store(dir: &std::path::Path, profiles: &[(&str, bool)]) -> Context<'static> {
+        let ctx = Context::Dir(dir.to_path_buf());
+        storage::save_config_internal(&ctx, &storage::AppConfig::default()).unwrap();
+        for (name, active) in profiles {
+            let id = storage::create_profile_internal(&ctx, name.to_string(), Some(format!("127.0.0.1 {}\n", name)), None, None).unwrap();
+            if *active {
+                storage::activate_profile_internal(&ctx, &id).unwrap();
+            }
+        }
+        ctx
+    }
+
+    fn active(ctx: &Context) -> Vec<String> {
+        storage::load_config_internal(ctx).unwrap().profiles.into_iter().filter(|p| p.active).map(|p| p.name).collect()
+    }
+
+    #[test]
+    fn exports_import_back_into_hostly() {
+        let source = tempfile::tempdir().unwrap();
+        let ctx = store(source.path(), &[("Dev", true), ("Test", false)]);
+        storage::save_common_config_internal(&ctx, "127.0.0.1 common".to_string()).unwrap();
+        let ads = storage::create_profile_internal(&ctx, "Ads".into(), Some("0.0.0.0 ads.test\n".into()), Some("https://a.test/hosts\nhttps://b.test/hosts".into()), Some(3600)).unwrap();
+        storage::set_profile_folder_internal(&ctx, &ads, Some("Lists/Ads".into())).unwrap();
+        let export = export_switchhosts_internal(&ctx).unwrap();
+        assert_eq!(export.warnings.len(), 1);
+
+        let target = tempfile::tempdir().unwrap();
+        let imported = store(target.path(), &[("Local", true)]);
+        let report = import_switchhosts_internal(&imported, export.json.clone()).unwrap();
+        assert!(report.common_restored);
+        assert_eq!(storage::load_common_config_internal(&imported).unwrap(), "127.0.0.1 common");
+        assert_eq!(active(&imported), ["Dev"]);
+        let config = storage::load_config_internal(&imported).unwrap();
+        let ads = config.profiles.iter().find(|p| p.name == "Ads").unwrap();
+        assert_eq!((ads.url.as_deref(), ads.update_interval, ads.folder.as_deref()), (Some("https://a.test/hosts"), Some(3600), Some("Lists/Ads")));
+        assert_eq!(storage::read_profile_file_internal(&imported, &ads.id).unwrap(), "0.0.0.0 ads.test\n");
+
+        // Importing into the store it came from only restores what differs
+        let report = import_switchhosts_internal(&ctx, export.json).unwrap();
+        let titles: Vec<&str> = report.skipped.iter().map(|s| s.title.as_str()).collect();
+        assert_eq!(titles, ["Dev", "Test"]);
+        assert_eq!(report.imported.len(), 1);
+    }
+
+    #[test]
+    fn common_config_does_not_switch_profiles_off() {
+        let source = tempfile::tempdir().unwrap();
+        let ctx = store(source.path(), &[("Dev", false)]);
+        storage::save_common_config_internal(&ctx, "127.0.0.1 common".to_string()).unwrap();
+        let export = export_switchhosts_internal(&ctx).unwrap();
+
+        let target = tempfile::tempdir().unwrap();
+        let imported = store(target.path(), &[("Local", true)]);
+        let report = import_switchhosts_internal(&imported, export.json).unwrap();
+        assert!(!report.multi_select);
+        assert_eq!(active(&imported), ["Local"]);
+    }
+}
@@ -0,0 +1,188 @@
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashSet};
+use std::net::IpAddr;
+
+/// Hostname pattern for include/exclude rules.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum HostPattern {
+    /// The domain itself and all of its subdomains
+    Suffix(String),
+    Regex(String),
+}
+
+/// Rules applied to the downloaded content of a remote profile before it is saved.
+#[derive(Debug, Serialize, Deserialize, Clone, Default, PartialEq)]
+pub struct TransformRules {
+    /// Keep only hostnames matching one of these; empty keeps everything
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub include: Vec<HostPattern>,
+    /// Drop hostnames matching one of these, checked after `include`
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub exclude: Vec<HostPattern>,
+    /// Address (exact, CIDR or prefix like `10.1.`) -> replacement address
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub rewrite_ips: BTreeMap<String, String>,
+    #[serde(default)]
+    pub strip_comments: bool,
+    /// Keep only the first entry for each hostname, which is the one that resolves anyway
+    #[serde(default)]
+    pub dedupe: bool,
+}
+
+enum Matcher {
+    Suffix(String),
+    Regex(Regex),
+}
+
+impl Matcher {
+    fn compile(pattern: &HostPattern) -> Result<Matcher, String> {
+        match pattern {
+            HostPattern::Suffix(s) => Ok(Matcher::Suffix(s.trim().trim_start_matches('.').to_ascii_lowercase())),
+            HostPattern::Regex(r) => Regex::new(r).map(Matcher::Regex).map_err(|e| format!("Invalid hostname regex '{}': {}", r, e)),
+        }
+    }
+
+    fn matches(&self, host: &str) -> bool {
+        match self {
+            Matcher::Suffix(s) => host == s || host.ends_with(&format!(".{}", s)),
+            Matcher::Regex(r) => r.is_match(host),
+        }
+    }
+}
+
+impl TransformRules {
+    pub fn is_empty(&self) -> bool {
+        *self == TransformRules::default()
+    }
+
+    /// Checks that every regex compiles and every rewrite target is an address.
+    pub fn validate(&self) -> Result<(), String> {
+        for p in self.include.iter().chain(&self.exclude) {
+            Matcher::compile(p)?;
+        }
+        for (from, to) in &self.rewrite_ips {
+            if to.parse::<IpAddr>().is_err() {
+                return Err(format!("Invalid rewrite target '{}' for '{}'", to, from));
+            }
+        }
+        Ok(())
+    }
+
+    /// Applies the rules line by line. Lines that aren't hosts entries are kept unless
+    /// `strip_comments` is set.
+    pub fn apply(&self, content: &str) -> Result<String, String> {
+        if self.is_empty() {
+            return Ok(content.to_string());
+        }
+        let include = self.include.iter().map(Matcher::compile).collect::<Result<Vec<_>, _>>()?;
+        let exclude = self.exclude.iter().map(Matcher::compile).collect::<Result<Vec<_>, _>>()?;
+        let rewrites = self.rewrite_ips.iter()
+            .map(|(from, to)| to.parse::<IpAddr>().map(|to| (from.as_str(), to)).map_err(|_| format!("Invalid rewrite target '{}' for '{}'", to, from)))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let mut seen = HashSet::new();
+        let mut out = String::new();
+        for line in content.lines() {
+            let (body, comment) = match line.find('#') {
+                Some(i) => (&line[..i], Some(&line[i..])),
+                None => (line, None),
+            };
+            let mut parts = body.split_whitespace();
+            let ip = parts.next().and_then(|ip| ip.parse::<IpAddr>().ok());
+            let Some(ip) = ip else {
+                // Comments, blank lines and anything we don't understand
+                if !self.strip_comments {
+                    out.push_str(line);
+                    out.push('\n');
+                } else if !body.trim().is_empty() {
+                    out.push_str(body.trim_end());
+                    out.push('\n');
+                }
+                continue;
+            };
+
+            let hosts: Vec<&str> = parts
+                .filter(|h| {
+                    let host = h.to_ascii_lowercase();
+                    (include.is_empty() || include.iter().any(|m| m.matches(&host)))
+                        && !exclude.iter().any(|m| m.matches(&host))
+                        && (!self.dedupe || seen.insert(host))
+                })
+                .collect();
+            if hosts.is_empty() {
+                continue;
+            }
+
+            let ip = rewrites.iter()
+                .find(|(from, _)| crate::network::ip_matches(from, &ip))
+                .map(|(_, to)| *to)
+                .unwrap_or(ip);
+            out.push_str(&format!("{} {}", ip, hosts.join(" ")));
+            if let (Some(comment), false) = (comment, self.strip_comments) {
+                out.push(' ');
+                out.push_str(comment);
+            }
+            out.push('\n');
+        }
+        Ok(out)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CONTENT: &str = "# header\n0.0.0.0 ads.example.com tracker.test\n10.1.2.3 intranet.corp # vpn\n0.0.0.0 ADS.example.com\nnot a hosts line\n";
+
+    #[test]
+    fn empty_rules_change_nothing() {
+        assert_eq!(TransformRules::default().apply(CONTENT).unwrap(), CONTENT);
+    }
+
+    #[test]
+    fn include_then_exclude() {
+        let rules = TransformRules {
+            include: vec![HostPattern::Suffix(".example.com".to_string()), HostPattern::Regex("^intra".to_string())],
+            exclude: vec![HostPattern::Suffix("intranet.corp".to_string())],
+            ..Default::default()
+        };
+        assert_eq!(
+            rules.apply(CONTENT).unwrap(),
+            "# header\n0.0.0.0 ads.example.com\n0.0.0.0 ADS.example.com\nnot a hosts line\n"
+        );
+    }
+
+    #[test]
+    fn suffix_does_not_match_partial_labels() {
+        let rules = TransformRules { exclude: vec![HostPattern::Suffix("example.com".to_string())], ..Default::default() };
+        assert_eq!(rules.apply("1.1.1.1 badexample.com example.com\n").unwrap(), "1.1.1.1 badexample.com\n");
+    }
+
+    #[test]
+    fn rewrite_strip_and_dedupe() {
+        let rules = TransformRules {
+            rewrite_ips: [("10.1.".to_string(), "192.168.0.1".to_string())].into_iter().collect(),
+            strip_comments: true,
+            dedupe: true,
+            ..Default::default()
+        };
+        assert_eq!(
+            rules.apply(CONTENT).unwrap(),
+            "0.0.0.0 ads.example.com tracker.test\n192.168.0.1 intranet.corp\nnot a hosts line\n"
+        );
+    }
+
+    #[test]
+    fn invalid_rules_are_rejected() {
+        let bad_regex = TransformRules { include: vec![HostPattern::Regex("(".to_string())], ..Default::default() };
+        assert!(bad_regex.validate().is_err());
+        assert!(bad_regex.apply(CONTENT).is_err());
+        let bad_target = TransformRules {
+            rewrite_ips: [("10.0.0.0/8".to_string(), "nowhere".to_string())].into_iter().collect(),
+            ..Default::default()
+        };
+        assert!(bad_target.validate().unwrap_err().contains("nowhere"));
+    }
+}
@@ -0,0 +1,189 @@
+use crate::events::{self, AppEvent};
+use crate::logging;
+use crate::storage::{self, Context, ProfileMetadata, StorageLayout};
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::SystemTime;
+use uuid::Uuid;
+
+/// Modification times of the profile files as of the last scan. `None` until the first scan,
+/// which only records the baseline.
+static SEEN: Mutex<Option<HashMap<PathBuf, SystemTime>>> = Mutex::new(None);
+
+fn modified(path: &Path) -> Option<SystemTime> {
+    fs::metadata(path).and_then(|m| m.modified()).ok()
+}
+
+/// Records a write made by Hostly itself so the next scan doesn't treat it as an outside edit.
+pub fn note_written(path: &Path) {
+    let mut seen = SEEN.lock().unwrap_or_else(|e| e.into_inner());
+    if let (Some(seen), Some(mtime)) = (seen.as_mut(), modified(path)) {
+        seen.insert(path.to_path_buf(), mtime);
+    }
+}
+
+fn scan(dir: &Path, files: &mut HashMap<PathBuf, SystemTime>) {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return;
+    };
+    for path in entries.flatten().map(|e| e.path()) {
+        if path.is_dir() {
+            scan(&path, files);
+        } else if path.extension().and_then(|e| e.to_str()) == Some("hosts") {
+            if let Some(mtime) = modified(&path) {
+                files.insert(path, mtime);
+            }
+        }
+    }
+}
+
+/// Picks up edits made outside Hostly in the named layout: changed files of active profiles
+/// are re-applied, new `*.hosts` files are registered as profiles. Returns `true` if the hosts
+/// file was re-applied.
+pub fn check_profile_files(ctx: &Context) -> Result<bool, String> {
+    let mut config = storage::load_config_internal(ctx)?;
+    if config.storage_layout != StorageLayout::Named {
+        *SEEN.lock().unwrap_or_else(|e| e.into_inner()) = None;
+        return Ok(false);
+    }
+
+    let dir = ctx.get_app_dir()?.join("profiles");
+    let mut current = HashMap::new();
+    scan(&dir, &mut current);
+
+    let changed: Vec<PathBuf> = {
+        let mut seen = SEEN.lock().unwrap_or_else(|e| e.into_inner());
+        let changed = match seen.as_ref() {
+            Some(previous) => current.iter()
+                .filter(|(path, mtime)| previous.get(*path) != Some(*mtime))
+                .map(|(path, _)| path.clone())
+                .collect(),
+            None => Vec::new(),
+        };
+        *seen = Some(current);
+        changed
+    };
+    if changed.is_empty() {
+        return Ok(false);
+    }
+
+    let mut paths = HashMap::new();
+    for p in &config.profiles {
+        paths.insert(storage::profile_file_path(ctx, &config, &p.id)?, p.id.clone());
+    }
+
+    let mut needs_apply = false;
+    let mut registered = Vec::new();
+    for path in changed {
+        match paths.get(&path) {
+            Some(id) => {
+                events::emit(ctx, AppEvent::ProfileUpdated { id: id.clone(), deleted: false });
+                if config.profiles.iter().any(|p| &p.id == id && p.active) {
+                    let path = path.display().to_string();
+                    logging::info(&format!("Profile file {} changed outside Hostly, re-applying...", path), &[("event", "file_changed"), ("path", &path)]);
+                    needs_apply = true;
+                }
+            }
+            None => match register_profile(&mut config, &dir, &path) {
+                Ok(name) => registered.push(name),
+                Err(other) => {
+                    let path = path.display().to_string();
+                    logging::warn(
+                        &format!("Ignoring {}, its name collides with profile '{}'. Rename the file to register it.", path, other),
+                        &[("event", "file_skipped"), ("path", &path)],
+                    );
+                }
+            },
+        }
+    }
+
+    if !registered.is_empty() {
+        storage::save_config_internal(ctx, &config)?;
+        for name in registered {
+            logging::info(&format!("Registered new profile file '{}'", name), &[("event", "file_registered"), ("profile", &name)]);
+            if let Some(p) = config.profiles.iter().find(|p| p.name == name) {
+                events::emit(ctx, AppEvent::ProfileUpdated { id: p.id.clone(), deleted: false });
+            }
+        }
+    }
+    if needs_apply {
+        storage::apply_config_internal(ctx)?;
+    }
+    Ok(needs_apply)
+}
+
+/// Adds a profile for a `*.hosts` file dropped into the profiles directory, taking the id and
+/// remote settings from its sidecar when there is one. A file whose name collides with an
+/// existing profile is left alone, renaming the profile would detach it from its file; the
+/// error holds the name of that profile.
+fn register_profile(config: &mut storage::AppConfig, dir: &Path, path: &Path) -> Result<String, String> {
+    let sidecar: Option<ProfileMetadata> = fs::read_to_string(path.with_extension("meta.json"))
+        .ok()
+        .and_then(|s| serde_json::from_str(&s).ok());
+
+    let stem = path.file_stem().map(|s| s.to_string_lossy().to_string()).unwrap_or_default();
+    let folder = path.parent()
+        .and_then(|p| p.strip_prefix(dir).ok())
+        .map(|rel| rel.components().map(|c| c.as_os_str().to_string_lossy().to_string()).collect::<Vec<_>>().join("/"))
+        .filter(|f| !f.is_empty());
+
+    let mut meta = sidecar.unwrap_or(ProfileMetadata {
+        name: stem.clone(),
+        ..Default::default()
+    });
+    // The file location wins over whatever the sidecar says, it's what the user sees
+    meta.name = stem;
+    meta.folder = folder;
+    meta.active = false;
+    if !storage::is_safe_profile_id(&meta.id) || config.profiles.iter().any(|p| p.id == meta.id) {
+        meta.id = Uuid::new_v4().to_string();
+    }
+    if let Some(other) = config.profiles.iter().find(|p| storage::same_file_name(&p.name, &meta.name)) {
+        return Err(other.name.clone());
+    }
+
+    let name = meta.name.clone();
+    config.profiles.push(meta);
+    Ok(name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // One test, SEEN is shared by the whole process
+    #[test]
+    fn dropped_files_are_registered_unless_their_name_collides() {
+        let dir = tempfile::tempdir().unwrap();
+        let ctx = Context::Dir(dir.path().to_path_buf());
+        let config = storage::AppConfig { storage_layout: StorageLayout::Named, ..Default::default() };
+        storage::save_config_internal(&ctx, &config).unwrap();
+        storage::create_profile_internal(&ctx, "Dev".into(), Some("127.0.0.1 dev\n".into()), None, None).unwrap();
+        let profiles = dir.path().join("profiles");
+        assert!(!check_profile_files(&ctx).unwrap());
+
+        fs::create_dir(profiles.join("team")).unwrap();
+        fs::write(profiles.join("team/Staging.hosts"), "10.0.0.1 staging\n").unwrap();
+        fs::write(profiles.join("dev.hosts"), "10.0.0.2 dev\n").unwrap();
+        assert!(!check_profile_files(&ctx).unwrap());
+
+        let config = storage::load_config_internal(&ctx).unwrap();
+        let names: Vec<&str> = config.profiles.iter().map(|p| p.name.as_str()).collect();
+        assert_eq!(names, ["Dev", "Staging"]);
+        let staging = &config.profiles[1];
+        assert_eq!(staging.folder.as_deref(), Some("team"));
+        assert_eq!(storage::read_profile_file_internal(&ctx, &staging.id).unwrap(), "10.0.0.1 staging\n");
+
+        // Editing the skipped file again must not register it either
+        fs::write(profiles.join("dev.hosts"), "10.0.0.3 dev\n").unwrap();
+        fs::write(profiles.join("extra.hosts"), "").unwrap();
+        check_profile_files(&ctx).unwrap();
+        let config = storage::load_config_internal(&ctx).unwrap();
+        let names: Vec<&str> = config.profiles.iter().map(|p| p.name.as_str()).collect();
+        assert_eq!(names, ["Dev", "Staging", "extra"]);
+        let dev = storage::find_profile_id_by_name_internal(&ctx, "Dev").unwrap().unwrap();
+        assert_eq!(storage::read_profile_file_internal(&ctx, &dev).unwrap(), "127.0.0.1 dev\n");
+    }
+}
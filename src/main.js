@@ -2,6 +2,7 @@ const tauri = window.__TAURI__ || {};
 const { invoke } = tauri.core || {};
 const { ask, message, open, save: saveDialog } = tauri.dialog || {};
 const { readTextFile, writeTextFile } = tauri.fs || {}; // We'll use backend commands instead
+const { listen } = tauri.event || {};
 
 console.log('Tauri APIs initialized:', {
     hasInvoke: !!invoke,
@@ -11,6 +12,7 @@ console.log('Tauri APIs initialized:', {
 
 // State
 let profileMetadata = [];
+let remoteStatus = {}; // id -> refresh state from list_profiles
 let currentProfileId = null;
 let commonConfig = '';
 let systemHosts = '';
@@ -199,18 +201,18 @@ async function loadData() {
         commonConfig = await invoke('load_common_config');
         console.log('Common config loaded');
         
+        const profiles = await invoke('list_profiles');
+        remoteStatus = {};
+        profiles.forEach(x => { if (x.remote) remoteStatus[x.id] = x.remote; });
+        
         renderList();
         
         // Refresh editor if common is active
         if (currentProfileId === 'common') {
             editor.value = commonConfig;
         } else if (currentProfileId && currentProfileId !== 'system') {
-            const p = profileMetadata.find(x => x.id === currentProfileId);
-            if (p) {
-                const content = await invoke('list_profiles'); 
-                const match = content.find(x => x.id === currentProfileId);
-                if (match) editor.value = match.content;
-            }
+            const match = profiles.find(x => x.id === currentProfileId);
+            if (match) editor.value = match.content;
         }
     } catch (e) {
         console.error('loadData error:', e);
@@ -280,7 +282,8 @@ async function updateRemoteProfile(id, name) {
 
 
 let statusBarTimer = null;
-let lastAutoRefreshTime = 0;
+// Remote profiles being refreshed right now, from `remote-refresh-started`/`-finished`
+const refreshingIds = new Set();
 
 function updateStatusBar(p) {
     if (p && p.url) {
@@ -312,7 +315,24 @@ function updateStatusBar(p) {
             
             // Next Update
             let nextText = '';
-            if (p.update_interval && p.update_interval > 0) {
+            const status = remoteStatus[p.id];
+            const failing = status && status.failure_count > 0;
+            nextUpdateTimeEl.classList.toggle('has-error', !!failing);
+            nextUpdateTimeEl.title = failing ? (status.last_error || '') : '';
+            if (refreshingIds.has(p.id)) {
+                nextText = '正在更新...';
+            } else if (failing) {
+                nextText = `刷新失败（连续 ${status.failure_count} 次）`;
+                if (status.next_attempt) {
+                    const retryTime = new Date(status.next_attempt);
+                    const diff = retryTime - new Date();
+                    if (diff <= 1000) {
+                        nextText += '，正在重试...';
+                    } else {
+                        nextText += `，下次重试：${formatDate(retryTime)} (还有 ${Math.floor(diff/1000)}秒)`;
+                    }
+                }
+            } else if (p.update_interval && p.update_interval > 0) {
                  let lastTime = p.last_update ? new Date(p.last_update) : null;
                  if (lastTime) {
                     const nextTime = new Date(lastTime.getTime() + p.update_interval * 1000);
@@ -320,15 +340,8 @@ function updateStatusBar(p) {
                     const now = new Date();
                     const diff =  nextTime - now;
                     
-                    if (diff <= 1000) { // If <= 1s remaining
+                    if (diff <= 1000) { // If <= 1s remaining, the scheduler reports back when done
                          nextText = '正在更新...';
-                         // Trigger check
-                         const nowTs = Date.now();
-                         if (nowTs - lastAutoRefreshTime > 2000) {
-                             lastAutoRefreshTime = nowTs;
-                             // Call loadData silently (no spinner on refresh button, but effective)
-                             loadData();
-                         }
                     } else {
                          nextText = `下次刷新：${formatDate(nextTime)} (还有 ${Math.floor(diff/1000)}秒)`;
                     }
@@ -338,6 +351,13 @@ function updateStatusBar(p) {
             } else {
                 nextText = '自动刷新：未开启';
             }
+            // Best-effort refreshes succeed with some sources down, say which
+            const failedSources = !failing && status && status.sources ? status.sources.filter(x => x.last_error) : [];
+            if (failedSources.length > 0) {
+                nextText += `（${failedSources.length}/${status.sources.length} 个源失败）`;
+                nextUpdateTimeEl.classList.add('has-error');
+                nextUpdateTimeEl.title = failedSources.map(x => `${x.url}: ${x.last_error}`).join('\n');
+            }
             nextUpdateTimeEl.innerText = nextText;
         };
         
@@ -367,6 +387,73 @@ async function manualRefreshRemote(id) {
     }
 }
 
+// Events usually come in bursts (switch + apply, refresh + apply), reload once per burst
+let reloadTimer = null;
+function scheduleReload() {
+    clearTimeout(reloadTimer);
+    reloadTimer = setTimeout(loadData, 150);
+}
+
+function refreshStatusBar(id) {
+    const p = profileMetadata.find(x => x.id === id);
+    if (p && currentProfileId === id) updateStatusBar(p);
+}
+
+// Re-reads the system hosts view, unless the user is editing it
+async function reloadSystemHosts() {
+    if (currentProfileId !== 'system' || !editor.readOnly) return;
+    try {
+        systemHosts = await invoke('get_system_hosts');
+        editor.value = systemHosts;
+    } catch (e) { console.error(e); }
+}
+
+// The backend reports every state change, whether it came from this window, the CLI, the
+// control API or the scheduler
+function listenForBackendEvents() {
+    if (!listen) return;
+    listen('profile-updated', (event) => {
+        const { id, deleted } = event.payload;
+        if (deleted && currentProfileId === id) {
+            currentProfileId = null;
+            editor.value = '';
+            currentNameDisplay.innerText = '请选择配置';
+            startStatusBarTimer(null);
+        }
+        scheduleReload();
+    });
+    listen('profile-activated', scheduleReload);
+    listen('apply-succeeded', reloadSystemHosts);
+    listen('apply-failed', (event) => {
+        showToast(`写入 hosts 失败: ${event.payload.error}`, 'error', 5000);
+    });
+    listen('remote-refresh-started', (event) => {
+        refreshingIds.add(event.payload.id);
+        refreshStatusBar(event.payload.id);
+    });
+    listen('remote-refresh-finished', (event) => {
+        const { id, changed, entry } = event.payload;
+        refreshingIds.delete(id);
+        refreshStatusBar(id);
+        // Failures show up in the status bar
+        if (changed) {
+            const p = profileMetadata.find(x => x.id === id);
+            showToast(`${p ? p.name : id} 已更新：新增 ${entry.added}，删除 ${entry.removed}，修改 ${entry.changed}`, 'info', 5000);
+        }
+        scheduleReload();
+    });
+    listen('hosts-file-drifted', (event) => {
+        showToast(`${event.payload.path} 已被其他程序修改`, 'info', 5000);
+        reloadSystemHosts();
+    });
+    // Commands from a second `hostly ...` launch run in this instance, their output goes back to its terminal
+    listen('cli-forwarded', (event) => {
+        showToast(`已执行命令：hostly ${event.payload.args.join(' ')}`, 'info');
+        // Not everything a command touches has an event of its own (common config, settings)
+        scheduleReload();
+    });
+}
+
 function startStatusBarTimer(id) {
     if (statusBarTimer) clearInterval(statusBarTimer);
     statusBarTimer = null;
@@ -522,12 +609,6 @@ async function toggleProfile(id) {
         if (p) {
             showToast(`${p.name} 已${p.active ? '启用' : '禁用'}`, 'success');
         }
-
-        // If current view is system hosts, refresh immediately
-        if (currentProfileId === 'system') {
-            const systemContent = await invoke('get_system_hosts');
-            editor.value = systemContent;
-        }
     } catch (e) {
         showToast(`切换失败: ${e}`, 'error');
     }
@@ -702,9 +783,13 @@ async function importSwitchHosts() {
         });
         if (selected) {
             const data = await invoke('import_file', { path: selected });
-            const count = await invoke('import_switchhosts', { jsonContent: data });
+            const report = await invoke('import_switchhosts', { jsonContent: data });
             await loadData();
-            showToast(`已从 SwitchHosts 导入 ${count} 个环境`, 'success');
+            const renamed = report.imported.filter(i => i.renamed).length;
+            let msg = `已从 SwitchHosts 导入 ${report.imported.length} 个环境`;
+            if (renamed) msg += `，重命名 ${renamed} 个`;
+            if (report.skipped.length) msg += `，跳过 ${report.skipped.length} 个`;
+            showToast(msg, 'success');
         }
     } catch (e) {
         showToast(`导入失败: ${e}`, 'error');
@@ -940,6 +1025,7 @@ window.addEventListener('DOMContentLoaded', async () => {
     await initSidebarWidth();
     await loadData();
     selectProfile('system');
+    listenForBackendEvents();
     // Show window only after everything is ready to avoid flash
     setTimeout(() => {
         invoke('show_main_window');